@@ -0,0 +1,130 @@
+use std::io::Result;
+
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use super::{Entry, Key, KeyValueStore, Value};
+
+enum Executor {
+    Owned(Runtime),
+    Borrowed(Handle),
+}
+
+/// Synchronous facade over any [`KeyValueStore`].
+///
+/// Every call blocks the current thread until the underlying future completes, so
+/// it must not be used from within an async context, where it panics.
+pub struct BlockingKeyValueStore<S> {
+    store: S,
+    executor: Executor,
+}
+
+impl<S: KeyValueStore> BlockingKeyValueStore<S> {
+    /// Drives `store` on a dedicated current-thread runtime.
+    pub fn new(store: S) -> Result<Self> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+
+        Ok(Self::with_runtime(store, runtime))
+    }
+
+    /// Drives `store` on the given runtime, which is dropped with the facade.
+    pub fn with_runtime(store: S, runtime: Runtime) -> Self {
+        BlockingKeyValueStore {
+            store,
+            executor: Executor::Owned(runtime),
+        }
+    }
+
+    /// Drives `store` on a runtime owned elsewhere. Calls panic when made from
+    /// within a runtime, such as a task spawned on `handle`; wrap them in
+    /// `tokio::task::spawn_blocking` there.
+    pub fn with_handle(store: S, handle: Handle) -> Self {
+        BlockingKeyValueStore {
+            store,
+            executor: Executor::Borrowed(handle),
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.store
+    }
+
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        match &self.executor {
+            Executor::Owned(runtime) => runtime.block_on(future),
+            Executor::Borrowed(handle) => handle.block_on(future),
+        }
+    }
+
+    pub fn insert<K: Into<Key>, V: Into<Value>>(&self, key: K, value: V) -> Result<()> {
+        self.block_on(self.store.insert(key, value))
+    }
+
+    pub fn remove<K: Into<Key>>(&self, key: K) -> Result<bool> {
+        self.block_on(self.store.remove(key))
+    }
+
+    pub fn exists<K: AsRef<[u8]>>(&self, key: K) -> Result<bool> {
+        self.block_on(self.store.exists(key))
+    }
+
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Entry>> {
+        self.block_on(self.store.get(key))
+    }
+
+    pub fn list<K: AsRef<[u8]>>(&self, prefix: K) -> Result<Vec<Key>> {
+        self.block_on(async { Ok(self.store.list(prefix).await?.collect()) })
+    }
+}
+
+#[cfg(all(test, feature = "memory"))]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryKeyValueStore;
+
+    fn exercise<S: KeyValueStore>(store: &BlockingKeyValueStore<S>) -> Result<()> {
+        store.insert(b"key".to_vec(), b"value".to_vec())?;
+
+        assert!(store.exists(b"key")?);
+        assert_eq!(store.get(b"key")?.unwrap().value.bytes, b"value");
+        assert_eq!(store.list(b"k")?.len(), 1);
+        assert!(store.remove(b"key".to_vec())?);
+        assert!(store.get(b"key")?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn owned_runtime() -> Result<()> {
+        let store = BlockingKeyValueStore::new(MemoryKeyValueStore::default())?;
+        exercise(&store)
+    }
+
+    #[test]
+    fn borrowed_handle() -> Result<()> {
+        let runtime = Builder::new_current_thread().build()?;
+        let store = BlockingKeyValueStore::with_handle(
+            MemoryKeyValueStore::default(),
+            runtime.handle().clone(),
+        );
+
+        exercise(&store)
+    }
+
+    #[test]
+    #[should_panic]
+    fn borrowed_handle_within_runtime() {
+        let runtime = Builder::new_current_thread().build().unwrap();
+        let store = BlockingKeyValueStore::with_handle(
+            MemoryKeyValueStore::default(),
+            runtime.handle().clone(),
+        );
+
+        runtime.block_on(async {
+            let _ = store.exists(b"key");
+        });
+    }
+}
@@ -1,13 +1,20 @@
 use std::{
     fs,
-    io::Result,
+    io::{Error, Result},
     path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU64, Ordering},
 };
 
-use tokio::{fs::File, io};
+use tokio::fs::File;
 
 use super::{Entry, Key, KeyValueStore, Value};
 
+/// Stores each key in a file of the directory, named after the base64url key.
+///
+/// Writes lock a `.<name>.lock` file next to the value, shared by `insert` and
+/// `remove` and exclusive for `update`, so that updates are atomic across the
+/// instances and processes using the directory. Lock files are left in place.
 #[derive(Clone)]
 pub struct DirectoryKeyValueStore {
     base: PathBuf,
@@ -29,41 +36,90 @@ impl DirectoryKeyValueStore {
     fn file_path<K: AsRef<[u8]>>(&self, key: K) -> PathBuf {
         self.base.join(base64_url::encode(key.as_ref()))
     }
-}
 
-impl KeyValueStore for DirectoryKeyValueStore {
-    async fn insert<K: Into<Key>, V: Into<Value>>(&self, key: K, value: V) -> Result<()> {
-        let path = self.file_path(key.into());
+    /// Locks the key stored at `path`, until the returned file is dropped.
+    async fn lock(path: &Path, exclusive: bool) -> Result<fs::File> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let path = path.with_file_name(format!(".{name}.lock"));
+
+        tokio::task::spawn_blocking(move || {
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(path)?;
+
+            match exclusive {
+                true => file.lock()?,
+                false => file.lock_shared()?,
+            }
+
+            Ok(file)
+        })
+        .await
+        .map_err(Error::other)?
+    }
+
+    async fn read(path: &Path) -> Result<Option<Value>> {
+        match File::open(path).await {
+            Ok(file) => Ok(Some(Value::from_reader(file).await?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
 
-        let mut file = File::create(path).await?;
-        io::copy(&mut value.into().bytes.as_slice(), &mut file).await?;
+    /// Writes to a temporary file and renames it over `path`, so that readers,
+    /// which take no lock, never see a partial value.
+    async fn write(path: &Path, value: &Value) -> Result<()> {
+        static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+        // base64url never starts with a dot, `list` skips these
+        let temp = path.with_file_name(format!(".{name}.{}.{sequence}", process::id()));
+
+        tokio::fs::write(&temp, &value.bytes).await?;
+
+        if let Err(e) = tokio::fs::rename(&temp, path).await {
+            let _ = tokio::fs::remove_file(&temp).await;
+            return Err(e);
+        }
 
         Ok(())
     }
 
-    async fn remove<K: Into<Key>>(&self, key: K) -> Result<bool> {
-        let path = self.file_path(key.into());
-        match std::fs::remove_file(&path) {
+    fn delete(path: &Path) -> Result<bool> {
+        match std::fs::remove_file(path) {
             Ok(()) => Ok(true),
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
             Err(e) => Err(e),
         }
     }
+}
+
+impl KeyValueStore for DirectoryKeyValueStore {
+    async fn insert<K: Into<Key>, V: Into<Value>>(&self, key: K, value: V) -> Result<()> {
+        let path = self.file_path(key.into());
+
+        let _lock = Self::lock(&path, false).await?;
+        Self::write(&path, &value.into()).await
+    }
+
+    async fn remove<K: Into<Key>>(&self, key: K) -> Result<bool> {
+        let path = self.file_path(key.into());
+
+        let _lock = Self::lock(&path, false).await?;
+        Self::delete(&path)
+    }
 
     async fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Entry>> {
         let path = self.file_path(key.as_ref());
-        match File::open(&path).await {
-            Ok(file) => {
-                let value = Value::from_reader(file).await?;
-                Ok(Some(Entry {
-                    key: Key::new(key.as_ref()),
-                    value,
-                    md5: None,
-                }))
-            }
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
-            Err(e) => Err(e),
-        }
+
+        Ok(Self::read(&path).await?.map(|value| Entry {
+            key: Key::new(key.as_ref()),
+            value,
+            md5: None,
+        }))
     }
 
     async fn exists<K: AsRef<[u8]>>(&self, key: K) -> Result<bool> {
@@ -78,6 +134,11 @@ impl KeyValueStore for DirectoryKeyValueStore {
 
             if path.is_file() {
                 let file_name = path.file_name().unwrap().to_str().unwrap();
+
+                if file_name.starts_with('.') {
+                    continue;
+                }
+
                 let key = base64_url::decode(file_name).unwrap();
 
                 if key.starts_with(prefix.as_ref()) {
@@ -88,4 +149,27 @@ impl KeyValueStore for DirectoryKeyValueStore {
 
         Ok(result.into_iter())
     }
+
+    async fn update<K, F>(&self, key: K, mut f: F) -> Result<Option<Value>>
+    where
+        K: Into<Key>,
+        F: FnMut(Option<&Value>) -> Result<Option<Value>>,
+    {
+        let path = self.file_path(key.into());
+
+        let _lock = Self::lock(&path, true).await?;
+
+        let current = Self::read(&path).await?;
+
+        match f(current.as_ref())? {
+            Some(value) => {
+                Self::write(&path, &value).await?;
+                Ok(Some(value))
+            }
+            None => {
+                Self::delete(&path)?;
+                Ok(None)
+            }
+        }
+    }
 }
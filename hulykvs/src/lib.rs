@@ -1,9 +1,18 @@
-use std::io::Result;
+use std::io::{Error, ErrorKind, Result};
 
+#[cfg(feature = "io")]
 use tokio::io::{AsyncRead, AsyncReadExt};
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "directory")]
 pub mod directory;
+#[cfg(feature = "memory")]
 pub mod memory;
+#[cfg(feature = "mirror")]
+pub mod mirror;
+#[cfg(feature = "versioned")]
+pub mod versioned;
 
 #[derive(Clone, Hash, PartialEq, Eq)]
 pub struct Slice {
@@ -17,6 +26,7 @@ impl Slice {
         }
     }
 
+    #[cfg(feature = "io")]
     pub async fn from_reader<R: AsyncRead + Unpin>(mut reader: R) -> Result<Self> {
         let mut bytes = Vec::new();
         reader.read_to_end(&mut bytes).await?;
@@ -24,6 +34,24 @@ impl Slice {
     }
 }
 
+impl Slice {
+    /// Decodes a counter value, see [`KeyValueStore::increment`].
+    pub fn to_i64(&self) -> Result<i64> {
+        std::str::from_utf8(&self.bytes)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "value is not an integer"))
+    }
+}
+
+impl From<i64> for Slice {
+    fn from(value: i64) -> Self {
+        Self {
+            bytes: value.to_string().into_bytes(),
+        }
+    }
+}
+
 impl From<Vec<u8>> for Slice {
     fn from(bytes: Vec<u8>) -> Self {
         Self { bytes }
@@ -77,4 +105,179 @@ pub trait KeyValueStore {
         &self,
         prefix: K,
     ) -> impl Future<Output = Result<impl Iterator<Item = Key>>>;
+
+    /// Atomically replaces the value of `key` with the result of `f`, which receives
+    /// the current value. Returning `None` removes the key, returning an error aborts
+    /// the update. Resolves to the stored value.
+    ///
+    /// The local backends lock the key while `f` runs, so `f` must not access the
+    /// same store, it would deadlock. Remote stores run a compare-and-swap loop
+    /// instead and call `f` again on conflict, so it may be called more than once.
+    fn update<K, F>(&self, key: K, f: F) -> impl Future<Output = Result<Option<Value>>>
+    where
+        K: Into<Key>,
+        F: FnMut(Option<&Value>) -> Result<Option<Value>>;
+
+    /// Atomically adds `delta` to the counter stored at `key` and returns the new value.
+    /// Counters are stored as the decimal ASCII representation of an `i64`, a missing
+    /// key counts as zero.
+    fn increment<K: Into<Key>>(&self, key: K, delta: i64) -> impl Future<Output = Result<i64>> {
+        async move {
+            let mut counter = 0;
+
+            self.update(key, |current| {
+                let value = current.map(Slice::to_i64).transpose()?.unwrap_or(0);
+
+                counter = value
+                    .checked_add(delta)
+                    .ok_or_else(|| Error::new(ErrorKind::InvalidData, "counter overflow"))?;
+
+                Ok(Some(counter.into()))
+            })
+            .await?;
+
+            Ok(counter)
+        }
+    }
+
+    /// Atomically subtracts `delta` from the counter stored at `key`, see [`Self::increment`].
+    fn decrement<K: Into<Key>>(&self, key: K, delta: i64) -> impl Future<Output = Result<i64>> {
+        async move {
+            let delta = delta
+                .checked_neg()
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "counter overflow"))?;
+
+            self.increment(key, delta).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn update(store: &impl KeyValueStore) -> Result<()> {
+        let stored = store.update(b"key".to_vec(), |current| {
+            assert!(current.is_none());
+            Ok(Some(b"a".to_vec().into()))
+        });
+        assert_eq!(stored.await?.unwrap().bytes, b"a");
+
+        let aborted = store.update(b"key".to_vec(), |_| Err(Error::other("abort")));
+        assert!(aborted.await.is_err());
+        assert_eq!(store.get(b"key").await?.unwrap().value.bytes, b"a");
+
+        let removed = store.update(b"key".to_vec(), |current| {
+            assert_eq!(current.unwrap().bytes, b"a");
+            Ok(None)
+        });
+        assert!(removed.await?.is_none());
+        assert!(!store.exists(b"key").await?);
+
+        Ok(())
+    }
+
+    async fn counters(store: &impl KeyValueStore) -> Result<()> {
+        assert_eq!(store.increment(b"counter".to_vec(), 5).await?, 5);
+        assert_eq!(store.decrement(b"counter".to_vec(), 8).await?, -3);
+        assert_eq!(store.get(b"counter").await?.unwrap().value.bytes, b"-3");
+
+        let (a, b, c) = tokio::join!(
+            store.increment(b"counter".to_vec(), 1),
+            store.increment(b"counter".to_vec(), 1),
+            store.increment(b"counter".to_vec(), 1),
+        );
+        // each sees a distinct value: -2, -1 and 0
+        assert_eq!(a? + b? + c?, -3);
+        assert_eq!(store.get(b"counter").await?.unwrap().value.to_i64()?, 0);
+
+        store.insert(b"counter".to_vec(), b"007".to_vec()).await?;
+        assert_eq!(store.increment(b"counter".to_vec(), 1).await?, 8);
+        assert_eq!(store.get(b"counter").await?.unwrap().value.bytes, b"8");
+
+        store.insert(b"counter".to_vec(), i64::MAX).await?;
+        let overflow = store.increment(b"counter".to_vec(), 1).await.unwrap_err();
+        assert_eq!(overflow.kind(), ErrorKind::InvalidData);
+        let overflow = store
+            .decrement(b"counter".to_vec(), i64::MIN)
+            .await
+            .unwrap_err();
+        assert_eq!(overflow.kind(), ErrorKind::InvalidData);
+
+        store.insert(b"text".to_vec(), b"1.5".to_vec()).await?;
+        let invalid = store.increment(b"text".to_vec(), 1).await.unwrap_err();
+        assert_eq!(invalid.kind(), ErrorKind::InvalidData);
+        assert_eq!(store.get(b"text").await?.unwrap().value.bytes, b"1.5");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "memory")]
+    #[tokio::test]
+    async fn memory_update() -> Result<()> {
+        let store = memory::MemoryKeyValueStore::default();
+
+        update(&store).await?;
+        counters(&store).await
+    }
+
+    #[cfg(feature = "directory")]
+    #[tokio::test]
+    async fn directory_update() -> Result<()> {
+        let base = std::env::temp_dir().join(format!("hulykvs-update-{}", std::process::id()));
+        let store = directory::DirectoryKeyValueStore::new(&base)?;
+
+        let result = async {
+            update(&store).await?;
+            counters(&store).await?;
+
+            // temporary files of the atomic writes are not left over, lock files
+            // are not listed
+            let files: Vec<_> = std::fs::read_dir(&base)?
+                .map(|entry| Ok(entry?.file_name().into_string().unwrap()))
+                .collect::<Result<_>>()?;
+            assert!(
+                files
+                    .iter()
+                    .all(|name| !name.starts_with('.') || name.ends_with(".lock"))
+            );
+            assert_eq!(store.list(b"").await?.count(), 2);
+
+            Ok(())
+        }
+        .await;
+
+        std::fs::remove_dir_all(&base)?;
+        result
+    }
+
+    #[cfg(feature = "directory")]
+    #[tokio::test]
+    async fn directory_shared_between_instances() -> Result<()> {
+        let base = std::env::temp_dir().join(format!("hulykvs-shared-{}", std::process::id()));
+        let a = directory::DirectoryKeyValueStore::new(&base)?;
+        let b = directory::DirectoryKeyValueStore::new(&base)?;
+
+        async fn increments(store: &impl KeyValueStore) -> Result<()> {
+            for _ in 0..20 {
+                store.increment(b"counter".to_vec(), 1).await?;
+            }
+            Ok(())
+        }
+
+        let result = async {
+            let (a, b) = tokio::join!(increments(&a), increments(&b));
+            a?;
+            b?;
+
+            let store = directory::DirectoryKeyValueStore::new(&base)?;
+            assert_eq!(store.get(b"counter").await?.unwrap().value.to_i64()?, 40);
+
+            Ok(())
+        }
+        .await;
+
+        std::fs::remove_dir_all(&base)?;
+        result
+    }
 }
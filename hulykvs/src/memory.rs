@@ -1,6 +1,6 @@
 use std::io::Result;
 
-use dashmap::DashMap;
+use dashmap::{DashMap, mapref::entry::Entry as MapEntry};
 
 use super::{Entry, Key, KeyValueStore, Value};
 
@@ -39,6 +39,33 @@ impl KeyValueStore for MemoryKeyValueStore {
             .filter(move |entry| entry.key().bytes.starts_with(prefix.as_ref()))
             .map(|entry| entry.key().to_owned()))
     }
+
+    async fn update<K, F>(&self, key: K, mut f: F) -> Result<Option<Value>>
+    where
+        K: Into<Key>,
+        F: FnMut(Option<&Value>) -> Result<Option<Value>>,
+    {
+        // the entry holds the shard lock until the new value is stored
+        match self.store.entry(key.into()) {
+            MapEntry::Occupied(mut entry) => match f(Some(entry.get()))? {
+                Some(value) => {
+                    entry.insert(value.clone());
+                    Ok(Some(value))
+                }
+                None => {
+                    entry.remove();
+                    Ok(None)
+                }
+            },
+            MapEntry::Vacant(entry) => match f(None)? {
+                Some(value) => {
+                    entry.insert(value.clone());
+                    Ok(Some(value))
+                }
+                None => Ok(None),
+            },
+        }
+    }
 }
 
 /*
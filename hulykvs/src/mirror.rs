@@ -0,0 +1,505 @@
+use std::{collections::HashSet, io::Result};
+
+use dashmap::DashSet;
+
+use super::{Entry, Key, KeyValueStore, Value};
+
+/// Outcome of a [`MirrorStore::reconcile`] pass.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Reconciliation {
+    /// Keys copied to the secondary because they were missing or different.
+    pub copied: usize,
+    /// Keys removed from the secondary because the primary does not have them.
+    pub removed: usize,
+}
+
+/// Replicates writes to a primary and a secondary store.
+///
+/// Writes must succeed on the primary. A failed write to the secondary does not
+/// fail the operation, the key is queued for repair instead. Reads are served
+/// from the primary and, if enabled, fall back to the secondary.
+pub struct MirrorStore<P, S> {
+    primary: P,
+    secondary: S,
+    fallback: bool,
+    repair: DashSet<Key>,
+}
+
+impl<P: KeyValueStore, S: KeyValueStore> MirrorStore<P, S> {
+    pub fn new(primary: P, secondary: S) -> Self {
+        MirrorStore {
+            primary,
+            secondary,
+            fallback: false,
+            repair: DashSet::new(),
+        }
+    }
+
+    /// Read from the secondary when the primary fails or misses a key.
+    /// Keys queued for repair are never read from the secondary, and a listing
+    /// does not fall back while a key under its prefix is queued for repair.
+    pub fn with_fallback(mut self, fallback: bool) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    pub fn secondary(&self) -> &S {
+        &self.secondary
+    }
+
+    /// Keys whose last write reached the primary but not the secondary.
+    pub fn pending_repairs(&self) -> Vec<Key> {
+        self.repair.iter().map(|key| key.clone()).collect()
+    }
+
+    /// Copies the primary state of every queued key to the secondary.
+    /// Returns the number of repaired keys, keys that fail again stay queued.
+    pub async fn repair(&self) -> Result<usize> {
+        let mut repaired = 0;
+
+        for key in self.pending_repairs() {
+            // dequeued before the copy, so that a write failing meanwhile queues it again
+            self.repair.remove(&key);
+
+            let result = match self.primary.get(&key).await {
+                Ok(Some(entry)) => self.secondary.insert(key.clone(), entry.value).await,
+                Ok(None) => self.secondary.remove(key.clone()).await.map(|_| ()),
+                Err(e) => {
+                    self.repair.insert(key);
+                    return Err(e);
+                }
+            };
+
+            match result {
+                Ok(()) => repaired += 1,
+                Err(_) => {
+                    self.repair.insert(key);
+                }
+            }
+        }
+
+        Ok(repaired)
+    }
+
+    /// Compares both stores under `prefix` and makes the secondary match the
+    /// primary: missing or different values are copied, extra keys are removed.
+    pub async fn reconcile<K: AsRef<[u8]>>(&self, prefix: K) -> Result<Reconciliation> {
+        let mut report = Reconciliation::default();
+
+        let primary_keys: HashSet<Key> = self.primary.list(prefix.as_ref()).await?.collect();
+        let secondary_keys: Vec<Key> = self.secondary.list(prefix.as_ref()).await?.collect();
+
+        for key in &primary_keys {
+            let queued = self.repair.remove(key).is_some();
+
+            let copied = async {
+                let Some(entry) = self.primary.get(key).await? else {
+                    // removed concurrently, the secondary is handled below
+                    return Ok(false);
+                };
+
+                let differs = match self.secondary.get(key).await? {
+                    Some(mirrored) => mirrored.value != entry.value,
+                    None => true,
+                };
+
+                if differs {
+                    self.secondary.insert(key.clone(), entry.value).await?;
+                }
+
+                Ok(differs)
+            }
+            .await;
+
+            match copied {
+                Ok(copied) => report.copied += usize::from(copied),
+                Err(e) => {
+                    if queued {
+                        self.repair.insert(key.clone());
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        for key in secondary_keys {
+            if !primary_keys.contains(&key) && !self.primary.exists(&key).await? {
+                let queued = self.repair.remove(&key).is_some();
+
+                if let Err(e) = self.secondary.remove(key.clone()).await {
+                    if queued {
+                        self.repair.insert(key);
+                    }
+                    return Err(e);
+                }
+
+                report.removed += 1;
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn may_fall_back(&self, key: &[u8]) -> bool {
+        self.fallback && !self.repair.contains(&Key::new(key))
+    }
+
+    fn may_list_fall_back(&self, prefix: &[u8]) -> bool {
+        self.fallback && !self.repair.iter().any(|key| key.bytes.starts_with(prefix))
+    }
+}
+
+impl<P: KeyValueStore, S: KeyValueStore> KeyValueStore for MirrorStore<P, S> {
+    async fn insert<K: Into<Key>, V: Into<Value>>(&self, key: K, value: V) -> Result<()> {
+        let key = key.into();
+        let value = value.into();
+
+        self.primary.insert(key.clone(), value.clone()).await?;
+
+        if self.secondary.insert(key.clone(), value).await.is_err() {
+            self.repair.insert(key);
+        }
+
+        Ok(())
+    }
+
+    async fn remove<K: Into<Key>>(&self, key: K) -> Result<bool> {
+        let key = key.into();
+
+        let removed = self.primary.remove(key.clone()).await?;
+
+        if self.secondary.remove(key.clone()).await.is_err() {
+            self.repair.insert(key);
+        }
+
+        Ok(removed)
+    }
+
+    async fn exists<K: AsRef<[u8]>>(&self, key: K) -> Result<bool> {
+        match self.primary.exists(key.as_ref()).await {
+            Ok(false) if self.may_fall_back(key.as_ref()) => self.secondary.exists(key).await,
+            Err(_) if self.may_fall_back(key.as_ref()) => self.secondary.exists(key).await,
+            result => result,
+        }
+    }
+
+    async fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Entry>> {
+        match self.primary.get(key.as_ref()).await {
+            Ok(None) if self.may_fall_back(key.as_ref()) => self.secondary.get(key).await,
+            Err(_) if self.may_fall_back(key.as_ref()) => self.secondary.get(key).await,
+            result => result,
+        }
+    }
+
+    async fn update<K, F>(&self, key: K, f: F) -> Result<Option<Value>>
+    where
+        K: Into<Key>,
+        F: FnMut(Option<&Value>) -> Result<Option<Value>>,
+    {
+        let key = key.into();
+
+        let value = self.primary.update(key.clone(), f).await?;
+
+        let mirrored = match &value {
+            Some(value) => self.secondary.insert(key.clone(), value.clone()).await,
+            None => self.secondary.remove(key.clone()).await.map(|_| ()),
+        };
+
+        if mirrored.is_err() {
+            self.repair.insert(key);
+        }
+
+        Ok(value)
+    }
+
+    async fn list<K: AsRef<[u8]>>(&self, prefix: K) -> Result<impl Iterator<Item = Key>> {
+        let keys: Result<Vec<Key>> = self
+            .primary
+            .list(prefix.as_ref())
+            .await
+            .map(Iterator::collect);
+
+        let keys = match keys {
+            Err(_) if self.may_list_fall_back(prefix.as_ref()) => {
+                self.secondary.list(prefix).await?.collect()
+            }
+            keys => keys?,
+        };
+
+        Ok(keys.into_iter())
+    }
+}
+
+#[cfg(all(test, feature = "memory"))]
+mod tests {
+    use std::{
+        io::Error,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    use super::*;
+    use crate::memory::MemoryKeyValueStore;
+
+    /// Memory store that fails every operation while `failing` is set, and yields
+    /// after reads while `yielding` is set.
+    #[derive(Default)]
+    struct Flaky {
+        store: MemoryKeyValueStore,
+        failing: AtomicBool,
+        yielding: AtomicBool,
+    }
+
+    impl Flaky {
+        fn fail(&self, failing: bool) {
+            self.failing.store(failing, Ordering::SeqCst);
+        }
+
+        fn yield_after_reads(&self, yielding: bool) {
+            self.yielding.store(yielding, Ordering::SeqCst);
+        }
+
+        fn check(&self) -> Result<()> {
+            match self.failing.load(Ordering::SeqCst) {
+                true => Err(Error::other("unavailable")),
+                false => Ok(()),
+            }
+        }
+    }
+
+    impl KeyValueStore for Flaky {
+        async fn insert<K: Into<Key>, V: Into<Value>>(&self, key: K, value: V) -> Result<()> {
+            self.check()?;
+            self.store.insert(key, value).await
+        }
+
+        async fn remove<K: Into<Key>>(&self, key: K) -> Result<bool> {
+            self.check()?;
+            self.store.remove(key).await
+        }
+
+        async fn exists<K: AsRef<[u8]>>(&self, key: K) -> Result<bool> {
+            self.check()?;
+            self.store.exists(key).await
+        }
+
+        async fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Entry>> {
+            self.check()?;
+            let entry = self.store.get(key).await;
+
+            if self.yielding.load(Ordering::SeqCst) {
+                tokio::task::yield_now().await;
+            }
+
+            entry
+        }
+
+        async fn update<K, F>(&self, key: K, f: F) -> Result<Option<Value>>
+        where
+            K: Into<Key>,
+            F: FnMut(Option<&Value>) -> Result<Option<Value>>,
+        {
+            self.check()?;
+            self.store.update(key, f).await
+        }
+
+        async fn list<K: AsRef<[u8]>>(&self, prefix: K) -> Result<impl Iterator<Item = Key>> {
+            self.check()?;
+            self.store.list(prefix).await
+        }
+    }
+
+    fn mirror() -> MirrorStore<Flaky, Flaky> {
+        MirrorStore::new(Flaky::default(), Flaky::default())
+    }
+
+    async fn value(store: &Flaky, key: &str) -> Option<Vec<u8>> {
+        let entry = store.store.get(key).await.unwrap();
+        entry.map(|entry| entry.value.bytes)
+    }
+
+    fn keys(keys: impl Iterator<Item = Key>) -> Vec<Vec<u8>> {
+        let mut keys: Vec<_> = keys.map(|key| key.bytes).collect();
+        keys.sort();
+        keys
+    }
+
+    #[tokio::test]
+    async fn writes_reach_both_stores() -> Result<()> {
+        let store = mirror();
+
+        store.insert(b"a".to_vec(), b"1".to_vec()).await?;
+        store.insert(b"b".to_vec(), b"2".to_vec()).await?;
+        store.increment(b"c".to_vec(), 3).await?;
+        assert!(store.remove(b"b".to_vec()).await?);
+
+        for backend in [store.primary(), store.secondary()] {
+            assert_eq!(value(backend, "a").await, Some(b"1".to_vec()));
+            assert_eq!(value(backend, "b").await, None);
+            assert_eq!(value(backend, "c").await, Some(b"3".to_vec()));
+        }
+
+        assert!(store.pending_repairs().is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn secondary_failures_are_repaired() -> Result<()> {
+        let store = mirror();
+
+        store.insert(b"b".to_vec(), b"1".to_vec()).await?;
+        store.secondary().fail(true);
+
+        store.insert(b"a".to_vec(), b"1".to_vec()).await?;
+        store.remove(b"b".to_vec()).await?;
+        store.increment(b"c".to_vec(), 1).await?;
+        assert_eq!(store.pending_repairs().len(), 3);
+
+        // still failing, nothing is repaired
+        assert_eq!(store.repair().await?, 0);
+        assert_eq!(store.pending_repairs().len(), 3);
+
+        store.secondary().fail(false);
+        assert_eq!(store.repair().await?, 3);
+        assert!(store.pending_repairs().is_empty());
+
+        assert_eq!(value(store.secondary(), "a").await, Some(b"1".to_vec()));
+        assert_eq!(value(store.secondary(), "b").await, None);
+        assert_eq!(value(store.secondary(), "c").await, Some(b"1".to_vec()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failures_during_repair_stay_queued() -> Result<()> {
+        let store = mirror();
+
+        store.secondary().fail(true);
+        store.insert(b"a".to_vec(), b"1".to_vec()).await?;
+        store.secondary().fail(false);
+
+        // the repair copies the value read before the second write, which fails
+        // on the secondary meanwhile
+        store.primary().yield_after_reads(true);
+        let (repaired, written) = tokio::join!(store.repair(), async {
+            store.secondary().fail(true);
+            let written = store.insert(b"a".to_vec(), b"2".to_vec()).await;
+            store.secondary().fail(false);
+            written
+        });
+        repaired?;
+        written?;
+
+        assert_eq!(value(store.secondary(), "a").await, Some(b"1".to_vec()));
+        assert_eq!(store.pending_repairs().len(), 1);
+
+        store.primary().yield_after_reads(false);
+        assert_eq!(store.repair().await?, 1);
+        assert_eq!(value(store.secondary(), "a").await, Some(b"2".to_vec()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn primary_failures_fail_writes() -> Result<()> {
+        let store = mirror();
+
+        store.primary().fail(true);
+        assert!(store.insert(b"a".to_vec(), b"1".to_vec()).await.is_err());
+        assert_eq!(value(store.secondary(), "a").await, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reconcile_under_prefix() -> Result<()> {
+        let store = mirror();
+
+        let primary = &store.primary().store;
+        primary.insert(b"p/a".to_vec(), b"1".to_vec()).await?;
+        primary.insert(b"p/b".to_vec(), b"2".to_vec()).await?;
+        primary.insert(b"q/a".to_vec(), b"1".to_vec()).await?;
+
+        let secondary = &store.secondary().store;
+        secondary.insert(b"p/b".to_vec(), b"old".to_vec()).await?;
+        secondary.insert(b"p/c".to_vec(), b"3".to_vec()).await?;
+        secondary.insert(b"q/c".to_vec(), b"3".to_vec()).await?;
+
+        let report = store.reconcile("p/").await?;
+        assert_eq!(
+            report,
+            Reconciliation {
+                copied: 2,
+                removed: 1
+            }
+        );
+
+        assert_eq!(value(store.secondary(), "p/a").await, Some(b"1".to_vec()));
+        assert_eq!(value(store.secondary(), "p/b").await, Some(b"2".to_vec()));
+        assert_eq!(value(store.secondary(), "p/c").await, None);
+        // outside of the prefix
+        assert_eq!(value(store.secondary(), "q/a").await, None);
+        assert_eq!(value(store.secondary(), "q/c").await, Some(b"3".to_vec()));
+
+        assert_eq!(store.reconcile("p/").await?, Reconciliation::default());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reads_fall_back_to_secondary() -> Result<()> {
+        let store = mirror();
+        store.insert(b"a".to_vec(), b"1".to_vec()).await?;
+        store
+            .secondary()
+            .store
+            .insert(b"b".to_vec(), b"2".to_vec())
+            .await?;
+
+        // disabled by default
+        assert!(store.get("b").await?.is_none());
+        store.primary().fail(true);
+        assert!(store.get("a").await.is_err());
+        assert!(store.list("").await.is_err());
+
+        let store = store.with_fallback(true);
+        assert_eq!(store.get("a").await?.unwrap().value.bytes, b"1");
+        assert!(store.exists("a").await?);
+        assert_eq!(keys(store.list("").await?), [b"a".to_vec(), b"b".to_vec()]);
+
+        store.primary().fail(false);
+        assert_eq!(store.get("b").await?.unwrap().value.bytes, b"2");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pending_keys_are_not_read_from_secondary() -> Result<()> {
+        let store = mirror().with_fallback(true);
+        store.insert(b"p/a".to_vec(), b"1".to_vec()).await?;
+
+        store.secondary().fail(true);
+        store.remove(b"p/a".to_vec()).await?;
+        store.secondary().fail(false);
+
+        // the secondary still has the removed key
+        assert!(store.get("p/a").await?.is_none());
+
+        store.primary().fail(true);
+        assert!(store.get("p/a").await.is_err());
+        assert!(store.list("p/").await.is_err());
+        assert!(store.list("q/").await.is_ok());
+
+        store.primary().fail(false);
+        assert_eq!(store.repair().await?, 1);
+
+        store.primary().fail(true);
+        assert_eq!(store.list("p/").await?.count(), 0);
+
+        Ok(())
+    }
+}
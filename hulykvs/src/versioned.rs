@@ -0,0 +1,491 @@
+use std::{
+    io::Result,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use dashmap::DashMap;
+use tokio::{
+    sync::{Mutex, OwnedMutexGuard},
+    time::Instant,
+};
+
+use super::{Entry, Key, KeyValueStore, Value};
+
+/// Metadata of a stored version, see [`VersionedStore::versions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Version {
+    pub number: u64,
+    pub timestamp: SystemTime,
+    pub size: usize,
+}
+
+/// Keeps the history of every written value of a store in a second store.
+///
+/// Each insert or update of a key records a new version, numbered from 1. Removing
+/// a key keeps its history, so a removed value can be restored. Versions beyond
+/// `max_versions` or older than `max_age` are pruned on write, the latest version
+/// is always kept.
+///
+/// Writes of a key are serialized, so that versions are recorded in the order the
+/// values were written. This only holds within a single `VersionedStore`: instances
+/// sharing the stores, in this process or others, must not write the same keys.
+///
+/// History keys are the length-prefixed key followed by the big endian version
+/// number, timestamp in milliseconds and size, the length-prefixed key alone holds
+/// the version counter. Timestamps are taken from the tokio clock.
+pub struct VersionedStore<S, H> {
+    store: S,
+    history: H,
+    max_versions: Option<usize>,
+    max_age: Option<Duration>,
+    locks: DashMap<Key, Arc<Mutex<()>>>,
+    epoch: (SystemTime, Instant),
+}
+
+impl<S: KeyValueStore, H: KeyValueStore> VersionedStore<S, H> {
+    pub const DEFAULT_MAX_VERSIONS: usize = 10;
+
+    pub fn new(store: S, history: H) -> Self {
+        VersionedStore {
+            store,
+            history,
+            max_versions: Some(Self::DEFAULT_MAX_VERSIONS),
+            max_age: None,
+            locks: DashMap::new(),
+            epoch: (SystemTime::now(), Instant::now()),
+        }
+    }
+
+    /// Keep at most `max_versions` versions per key, `None` keeps all of them.
+    pub fn with_max_versions(mut self, max_versions: Option<usize>) -> Self {
+        self.max_versions = max_versions;
+        self
+    }
+
+    /// Drop versions older than `max_age`, `None` keeps them regardless of age.
+    pub fn with_max_age(mut self, max_age: Option<Duration>) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn history(&self) -> &H {
+        &self.history
+    }
+
+    /// Lists the retained versions of `key`, oldest first.
+    pub async fn versions<K: AsRef<[u8]>>(&self, key: K) -> Result<Vec<Version>> {
+        let prefix = history_prefix(key.as_ref());
+
+        let mut versions: Vec<Version> = self
+            .history
+            .list(&prefix)
+            .await?
+            .filter_map(|k| parse_version(&prefix, &k))
+            .collect();
+
+        versions.sort_by_key(|version| version.number);
+
+        Ok(versions)
+    }
+
+    /// Fetches the value of `key` as of version `number`.
+    pub async fn get_version<K: AsRef<[u8]>>(&self, key: K, number: u64) -> Result<Option<Entry>> {
+        let prefix = history_prefix(key.as_ref());
+
+        let mut number_prefix = prefix.bytes.clone();
+        number_prefix.extend_from_slice(&number.to_be_bytes());
+
+        let version = self
+            .history
+            .list(&number_prefix)
+            .await?
+            .find_map(|k| parse_version(&prefix, &k));
+
+        let Some(version) = version else {
+            return Ok(None);
+        };
+
+        Ok(self
+            .history
+            .get(version_key(&prefix, &version))
+            .await?
+            .map(|entry| Entry {
+                key: Key::new(key.as_ref()),
+                value: entry.value,
+                md5: None,
+            }))
+    }
+
+    /// Makes version `number` the current value of `key`, recording it as a new version.
+    /// Returns `false` if the version does not exist.
+    pub async fn restore<K: Into<Key>>(&self, key: K, number: u64) -> Result<bool> {
+        let key = key.into();
+
+        match self.get_version(&key, number).await? {
+            Some(entry) => {
+                self.insert(key, entry.value).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Applies the retention settings to the history of `key`.
+    pub async fn prune<K: Into<Key>>(&self, key: K) -> Result<()> {
+        let key = key.into();
+
+        let _guard = self.lock(&key).await;
+        self.prune_history(&key).await
+    }
+
+    async fn prune_history(&self, key: &Key) -> Result<()> {
+        if self.max_versions.is_none() && self.max_age.is_none() {
+            return Ok(());
+        }
+
+        let prefix = history_prefix(key.as_ref());
+
+        let mut versions = self.versions(key).await?;
+        versions.reverse();
+
+        let now = self.now();
+
+        for (index, version) in versions.iter().enumerate().skip(1) {
+            let excess = self.max_versions.is_some_and(|max| index >= max);
+            let expired = self.max_age.is_some_and(|max_age| {
+                now.duration_since(version.timestamp)
+                    .is_ok_and(|age| age > max_age)
+            });
+
+            if excess || expired {
+                self.history.remove(version_key(&prefix, version)).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn record(&self, key: &Key, value: &Value) -> Result<()> {
+        let prefix = history_prefix(key.as_ref());
+
+        let number = self.history.increment(prefix.clone(), 1).await?;
+
+        let version = Version {
+            number: number as u64,
+            timestamp: self.now(),
+            size: value.bytes.len(),
+        };
+
+        self.history
+            .insert(version_key(&prefix, &version), value.clone())
+            .await?;
+
+        self.prune_history(key).await
+    }
+
+    fn now(&self) -> SystemTime {
+        let (system, instant) = self.epoch;
+        system + instant.elapsed()
+    }
+
+    /// Waits for the writes of `key` in progress, the entry is dropped with the
+    /// last guard.
+    async fn lock(&self, key: &Key) -> KeyGuard<'_> {
+        let lock = self.locks.entry(key.clone()).or_default().clone();
+
+        KeyGuard {
+            locks: &self.locks,
+            key: key.clone(),
+            guard: Some(lock.lock_owned().await),
+        }
+    }
+}
+
+struct KeyGuard<'a> {
+    locks: &'a DashMap<Key, Arc<Mutex<()>>>,
+    key: Key,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl Drop for KeyGuard<'_> {
+    fn drop(&mut self) {
+        self.guard.take();
+        // the shard lock keeps others from taking the mutex meanwhile
+        self.locks
+            .remove_if(&self.key, |_, lock| Arc::strong_count(lock) == 1);
+    }
+}
+
+impl<S: KeyValueStore, H: KeyValueStore> KeyValueStore for VersionedStore<S, H> {
+    async fn insert<K: Into<Key>, V: Into<Value>>(&self, key: K, value: V) -> Result<()> {
+        let key = key.into();
+        let value = value.into();
+
+        let _guard = self.lock(&key).await;
+
+        self.store.insert(key.clone(), value.clone()).await?;
+        self.record(&key, &value).await
+    }
+
+    async fn remove<K: Into<Key>>(&self, key: K) -> Result<bool> {
+        self.store.remove(key).await
+    }
+
+    async fn exists<K: AsRef<[u8]>>(&self, key: K) -> Result<bool> {
+        self.store.exists(key).await
+    }
+
+    async fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Entry>> {
+        self.store.get(key).await
+    }
+
+    async fn list<K: AsRef<[u8]>>(&self, prefix: K) -> Result<impl Iterator<Item = Key>> {
+        self.store.list(prefix).await
+    }
+
+    async fn update<K, F>(&self, key: K, f: F) -> Result<Option<Value>>
+    where
+        K: Into<Key>,
+        F: FnMut(Option<&Value>) -> Result<Option<Value>>,
+    {
+        let key = key.into();
+
+        let _guard = self.lock(&key).await;
+
+        let value = self.store.update(key.clone(), f).await?;
+
+        if let Some(value) = &value {
+            self.record(&key, value).await?;
+        }
+
+        Ok(value)
+    }
+}
+
+fn history_prefix(key: &[u8]) -> Key {
+    let mut bytes = Vec::with_capacity(4 + key.len());
+    bytes.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(key);
+    bytes.into()
+}
+
+fn version_key(prefix: &Key, version: &Version) -> Key {
+    let millis = version
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+
+    let mut bytes = prefix.bytes.clone();
+    bytes.extend_from_slice(&version.number.to_be_bytes());
+    bytes.extend_from_slice(&millis.to_be_bytes());
+    bytes.extend_from_slice(&(version.size as u64).to_be_bytes());
+    bytes.into()
+}
+
+fn parse_version(prefix: &Key, key: &Key) -> Option<Version> {
+    let suffix = key.bytes.strip_prefix(prefix.bytes.as_slice())?;
+    let suffix: &[u8; 24] = suffix.try_into().ok()?;
+    let (fields, _) = suffix.as_chunks::<8>();
+
+    Some(Version {
+        number: u64::from_be_bytes(fields[0]),
+        timestamp: UNIX_EPOCH + Duration::from_millis(u64::from_be_bytes(fields[1])),
+        size: u64::from_be_bytes(fields[2]) as usize,
+    })
+}
+
+#[cfg(all(test, feature = "memory"))]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryKeyValueStore;
+
+    type Store = VersionedStore<MemoryKeyValueStore, MemoryKeyValueStore>;
+
+    fn store() -> Store {
+        VersionedStore::new(
+            MemoryKeyValueStore::default(),
+            MemoryKeyValueStore::default(),
+        )
+    }
+
+    async fn numbers(store: &Store, key: &[u8]) -> Result<Vec<u64>> {
+        let versions = store.versions(key).await?;
+        Ok(versions.iter().map(|version| version.number).collect())
+    }
+
+    #[tokio::test]
+    async fn prune_by_count() -> Result<()> {
+        let store = store().with_max_versions(Some(3));
+
+        for value in 1..=5 {
+            store.insert(b"key".to_vec(), value).await?;
+        }
+
+        assert_eq!(numbers(&store, b"key").await?, [3, 4, 5]);
+        assert!(store.get_version(b"key", 2).await?.is_none());
+        assert_eq!(
+            store.get_version(b"key", 3).await?.unwrap().value.bytes,
+            b"3"
+        );
+
+        // the latest version is kept whatever the limit
+        let store = store.with_max_versions(Some(0));
+        store.increment(b"key".to_vec(), 1).await?;
+        assert_eq!(numbers(&store, b"key").await?, [6]);
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn prune_by_age() -> Result<()> {
+        let store = store()
+            .with_max_versions(None)
+            .with_max_age(Some(Duration::from_secs(60)));
+
+        store.insert(b"key".to_vec(), 1).await?;
+        store.insert(b"key".to_vec(), 2).await?;
+        tokio::time::advance(Duration::from_secs(120)).await;
+        store.insert(b"key".to_vec(), 3).await?;
+        store.insert(b"key".to_vec(), 4).await?;
+
+        assert_eq!(numbers(&store, b"key").await?, [3, 4]);
+
+        tokio::time::advance(Duration::from_secs(120)).await;
+        store.prune(b"key".to_vec()).await?;
+        assert_eq!(numbers(&store, b"key").await?, [4]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unlimited_history() -> Result<()> {
+        let store = store().with_max_versions(None);
+
+        for value in 1..=20 {
+            store.insert(b"key".to_vec(), value).await?;
+        }
+
+        let versions = store.versions(b"key").await?;
+        assert_eq!(versions.len(), 20);
+        assert_eq!(versions[19].size, 2);
+
+        Ok(())
+    }
+
+    /// Applies writes at once but completes them only after yielding as many times
+    /// as the value says, so that later writes complete first.
+    #[derive(Default)]
+    struct Delayed(MemoryKeyValueStore);
+
+    impl KeyValueStore for Delayed {
+        async fn insert<K: Into<Key>, V: Into<Value>>(&self, key: K, value: V) -> Result<()> {
+            let value = value.into();
+            let delay = value.to_i64()?;
+
+            self.0.insert(key, value).await?;
+
+            for _ in 0..delay {
+                tokio::task::yield_now().await;
+            }
+
+            Ok(())
+        }
+
+        async fn remove<K: Into<Key>>(&self, key: K) -> Result<bool> {
+            self.0.remove(key).await
+        }
+
+        async fn exists<K: AsRef<[u8]>>(&self, key: K) -> Result<bool> {
+            self.0.exists(key).await
+        }
+
+        async fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Entry>> {
+            self.0.get(key).await
+        }
+
+        async fn list<K: AsRef<[u8]>>(&self, prefix: K) -> Result<impl Iterator<Item = Key>> {
+            self.0.list(prefix).await
+        }
+
+        async fn update<K, F>(&self, key: K, f: F) -> Result<Option<Value>>
+        where
+            K: Into<Key>,
+            F: FnMut(Option<&Value>) -> Result<Option<Value>>,
+        {
+            self.0.update(key, f).await
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_writes() -> Result<()> {
+        let store = VersionedStore::new(Delayed::default(), MemoryKeyValueStore::default());
+
+        let (a, b, c) = tokio::join!(
+            store.insert(b"key".to_vec(), 3),
+            store.insert(b"key".to_vec(), 2),
+            store.insert(b"key".to_vec(), 1),
+        );
+        a?;
+        b?;
+        c?;
+
+        // the latest version is the current value
+        let current = store.get(b"key").await?.unwrap().value;
+        let latest = store.get_version(b"key", 3).await?.unwrap().value;
+        assert_eq!(latest.bytes, current.bytes);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn restore_old_version() -> Result<()> {
+        let store = store();
+
+        store.insert(b"key".to_vec(), b"first".to_vec()).await?;
+        store.insert(b"key".to_vec(), b"second".to_vec()).await?;
+        assert!(store.remove(b"key".to_vec()).await?);
+
+        assert!(store.restore(b"key".to_vec(), 1).await?);
+        assert_eq!(store.get(b"key").await?.unwrap().value.bytes, b"first");
+
+        let versions = store.versions(b"key").await?;
+        assert_eq!(versions.len(), 3);
+        assert_eq!(versions[2].number, 3);
+        assert_eq!(versions[2].size, 5);
+
+        assert!(!store.restore(b"key".to_vec(), 7).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keys_prefixing_other_keys() -> Result<()> {
+        let store = store();
+
+        // the second key is the first one followed by a version number
+        let mut tricky = b"a".to_vec();
+        tricky.extend_from_slice(&1u64.to_be_bytes());
+
+        store.insert(b"a".to_vec(), b"a".to_vec()).await?;
+        store.insert(b"ab".to_vec(), b"ab".to_vec()).await?;
+        store.insert(b"ab".to_vec(), b"ab2".to_vec()).await?;
+        store.insert(tricky.clone(), b"tricky".to_vec()).await?;
+
+        assert_eq!(numbers(&store, b"a").await?, [1]);
+        assert_eq!(numbers(&store, b"ab").await?, [1, 2]);
+        assert_eq!(numbers(&store, &tricky).await?, [1]);
+        assert_eq!(store.get_version(b"a", 1).await?.unwrap().value.bytes, b"a");
+        assert_eq!(
+            store.get_version(&tricky, 1).await?.unwrap().value.bytes,
+            b"tricky"
+        );
+
+        Ok(())
+    }
+}
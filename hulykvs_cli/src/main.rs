@@ -0,0 +1,386 @@
+//
+// Copyright © 2025 Hardcore Engineering Inc.
+//
+// Licensed under the Eclipse Public License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License. You may
+// obtain a copy of the License at https://www.eclipse.org/legal/epl-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use std::path::PathBuf;
+
+use anyhow::{Context, bail};
+use clap::{Args, Parser, Subcommand};
+use hex::FromHex;
+use hulykvs::{KeyValueStore, directory::DirectoryKeyValueStore};
+use hulykvs_client::{Condition, HttpKeyValueStore, ListQuery, Order};
+use jsonwebtoken::{EncodingKey, Header};
+use secrecy::SecretString;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use uuid::Uuid;
+
+/// Command-line client for hulykvs (API v2)
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(flatten)]
+    auth: Auth,
+
+    /// Server endpoint
+    #[arg(long, env = "HULYKVS_URL", default_value = "http://localhost:8094")]
+    url: String,
+
+    /// Workspace UUID
+    #[arg(long, short, env = "HULYKVS_WORKSPACE")]
+    workspace: Option<Uuid>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct Auth {
+    /// Bearer token, minted from --secret if omitted
+    #[arg(long, env = "HULYKVS_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// Secret used to sign tokens
+    #[arg(long, env = "HULY_TOKEN_SECRET", hide_env_values = true)]
+    secret: Option<String>,
+
+    /// Account of minted tokens
+    #[arg(long, default_value_t = Uuid::nil())]
+    account: Uuid,
+
+    /// Service name of minted tokens
+    #[arg(long, default_value = "hulykvs-cli")]
+    service: String,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the value of a key to stdout and its ETag to stderr
+    Get {
+        namespace: String,
+        key: String,
+        /// Write the value to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Store a value read from a file or stdin and print its ETag
+    Put {
+        namespace: String,
+        key: String,
+        /// Read the value from a file instead of stdin
+        #[arg(long, short)]
+        file: Option<PathBuf>,
+        /// Update only if the current ETag matches ('*' for any existing value)
+        #[arg(long, conflicts_with = "if_none_match")]
+        if_match: Option<String>,
+        /// Write only if the current ETag differs ('*' to insert only if the key does not exist)
+        #[arg(long)]
+        if_none_match: Option<String>,
+    },
+
+    /// Delete a key
+    Delete {
+        namespace: String,
+        key: String,
+        /// Delete only if the current ETag matches
+        #[arg(long)]
+        if_match: Option<String>,
+    },
+
+    /// List keys of a namespace
+    List {
+        namespace: String,
+        #[arg(long, short)]
+        prefix: Option<String>,
+        /// Keys per page, the cursor of the next page is printed to stderr
+        #[arg(long, short)]
+        limit: Option<u32>,
+        /// Cursor printed by the previous page
+        #[arg(long)]
+        cursor: Option<String>,
+        /// Descending order
+        #[arg(long)]
+        desc: bool,
+        /// Group keys by their prefix up to the delimiter, groups are printed with a trailing delimiter
+        #[arg(long, short)]
+        delimiter: Option<String>,
+    },
+
+    /// Save a namespace to a directory, one file per key
+    Dump {
+        namespace: String,
+        directory: PathBuf,
+        #[arg(long, short)]
+        prefix: Option<String>,
+    },
+
+    /// Upload a directory created by `dump` to a namespace
+    Load {
+        namespace: String,
+        directory: PathBuf,
+    },
+
+    /// Mint a token signed with --secret, like scripts/token.sh
+    Token {
+        /// JSON claims file, built from --workspace, --account and --service if omitted
+        #[arg(long)]
+        claims: Option<PathBuf>,
+    },
+}
+
+impl Cli {
+    fn claims(&self) -> serde_json::Value {
+        let mut claims = serde_json::json!({
+            "account": self.auth.account,
+            "extra": { "service": self.auth.service },
+        });
+
+        if let Some(workspace) = self.workspace {
+            claims["workspace"] = serde_json::json!(workspace);
+        }
+
+        claims
+    }
+
+    fn mint(&self, claims: &serde_json::Value) -> anyhow::Result<String> {
+        let Some(secret) = &self.auth.secret else {
+            bail!("either --token or --secret is required");
+        };
+
+        Ok(jsonwebtoken::encode(
+            &Header::default(),
+            claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )?)
+    }
+
+    fn store(&self, namespace: &str) -> anyhow::Result<HttpKeyValueStore> {
+        let workspace = self.workspace.context("--workspace is required")?;
+
+        let token = match &self.auth.token {
+            Some(token) => token.clone(),
+            None => self.mint(&self.claims())?,
+        };
+
+        Ok(HttpKeyValueStore::new(
+            &self.url,
+            SecretString::from(token),
+            workspace,
+            namespace,
+        )?)
+    }
+}
+
+fn parse_etag(value: &str) -> anyhow::Result<[u8; 16]> {
+    let value = value.trim();
+    let value = value.strip_prefix("W/").unwrap_or(value);
+
+    <[u8; 16]>::from_hex(value.trim_matches('"')).with_context(|| format!("invalid ETag: {value}"))
+}
+
+async fn copy<F: KeyValueStore, T: KeyValueStore>(
+    from: &F,
+    to: &T,
+    prefix: &str,
+) -> anyhow::Result<usize> {
+    let keys: Vec<_> = from.list(prefix).await?.collect();
+    let mut count = 0;
+
+    for key in keys {
+        // keys removed in the meantime are skipped
+        if let Some(entry) = from.get(&key).await? {
+            to.insert(key, entry.value).await?;
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match &cli.command {
+        Command::Get {
+            namespace,
+            key,
+            output,
+        } => {
+            let store = cli.store(namespace)?;
+
+            let Some(entry) = store.get(key).await? else {
+                bail!("key not found: {key}");
+            };
+
+            if let Some(md5) = entry.md5 {
+                eprintln!("ETag: {}", hex::encode(md5));
+            }
+
+            match output {
+                Some(path) => tokio::fs::write(path, &entry.value.bytes).await?,
+                None => {
+                    let mut stdout = tokio::io::stdout();
+                    stdout.write_all(&entry.value.bytes).await?;
+                    stdout.flush().await?;
+                }
+            }
+        }
+
+        Command::Put {
+            namespace,
+            key,
+            file,
+            if_match,
+            if_none_match,
+        } => {
+            let store = cli.store(namespace)?;
+
+            let value = match file {
+                Some(path) => tokio::fs::read(path).await?,
+                None => {
+                    let mut value = Vec::new();
+                    tokio::io::stdin().read_to_end(&mut value).await?;
+                    value
+                }
+            };
+
+            let condition = match (if_match.as_deref(), if_none_match.as_deref()) {
+                (Some("*"), _) => Condition::Exists,
+                (Some(etag), _) => Condition::Matches(parse_etag(etag)?),
+                (None, Some("*")) => Condition::Absent,
+                (None, Some(etag)) => Condition::Differs(parse_etag(etag)?),
+                (None, None) => Condition::None,
+            };
+
+            match store.put(key, value, condition).await? {
+                Some(md5) => println!("{}", hex::encode(md5)),
+                None => bail!("precondition failed"),
+            }
+        }
+
+        Command::Delete {
+            namespace,
+            key,
+            if_match,
+        } => {
+            let store = cli.store(namespace)?;
+
+            match if_match {
+                Some(etag) => {
+                    if !store.delete_if(key, parse_etag(etag)?).await? {
+                        bail!("precondition failed");
+                    }
+                }
+                None => {
+                    if !store.delete(key).await? {
+                        bail!("key not found: {key}");
+                    }
+                }
+            }
+        }
+
+        Command::List {
+            namespace,
+            prefix,
+            limit,
+            cursor,
+            desc,
+            delimiter,
+        } => {
+            let query = ListQuery {
+                prefix: prefix.clone(),
+                limit: *limit,
+                cursor: cursor.clone(),
+                order: if *desc { Order::Desc } else { Order::Asc },
+                delimiter: delimiter.clone(),
+                ..Default::default()
+            };
+
+            let response = cli.store(namespace)?.list_page(&query).await?;
+
+            for prefix in response.common_prefixes {
+                println!("{prefix}");
+            }
+
+            for key in response.keys {
+                println!("{key}");
+            }
+
+            if let Some(cursor) = response.next_cursor {
+                eprintln!("next cursor: {cursor}");
+            }
+        }
+
+        Command::Dump {
+            namespace,
+            directory,
+            prefix,
+        } => {
+            let store = cli.store(namespace)?;
+            let target = DirectoryKeyValueStore::new(directory)?;
+
+            let count = copy(&store, &target, prefix.as_deref().unwrap_or_default()).await?;
+            eprintln!("dumped {count} keys to {}", directory.display());
+        }
+
+        Command::Load {
+            namespace,
+            directory,
+        } => {
+            let store = cli.store(namespace)?;
+            let source = DirectoryKeyValueStore::new(directory)?;
+
+            let count = copy(&source, &store, "").await?;
+            eprintln!("loaded {count} keys into {namespace}");
+        }
+
+        Command::Token { claims } => {
+            let claims = match claims {
+                Some(path) => serde_json::from_slice(&tokio::fs::read(path).await?)?,
+                None => cli.claims(),
+            };
+
+            println!("{}", cli.mint(&claims)?);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn etags() {
+        let hex = "5d41402abc4b2a76b9719d911017c592";
+        let md5 = <[u8; 16]>::from_hex(hex).unwrap();
+
+        assert_eq!(parse_etag(hex).unwrap(), md5);
+        assert_eq!(parse_etag(&format!("\"{hex}\"")).unwrap(), md5);
+        assert_eq!(parse_etag(&format!(" W/\"{hex}\" ")).unwrap(), md5);
+
+        assert!(parse_etag("*").is_err());
+        assert!(parse_etag(&hex[1..]).is_err());
+        assert!(parse_etag("\"not an md5 hex string at all!\"").is_err());
+    }
+}
@@ -0,0 +1,722 @@
+//
+// Copyright © 2025 Hardcore Engineering Inc.
+//
+// Licensed under the Eclipse Public License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License. You may
+// obtain a copy of the License at https://www.eclipse.org/legal/epl-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    time::Duration,
+};
+
+use base64::prelude::*;
+use hex::FromHex;
+use hulykvs::{Entry, Key, KeyValueStore, Value};
+use reqwest::{
+    Client, RequestBuilder, Response, StatusCode, Url,
+    header::{ETAG, HeaderValue, IF_MATCH, IF_NONE_MATCH},
+};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Precondition of a write, see [`HttpKeyValueStore::put`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    /// Unconditional upsert.
+    None,
+    /// `If-Match: *`, the key must exist.
+    Exists,
+    /// `If-None-Match: *`, the key must not exist.
+    Absent,
+    /// `If-Match: <md5>`, the current value must have the given md5.
+    Matches([u8; 16]),
+    /// `If-None-Match: <md5>`, the key must not exist or have a different md5.
+    Differs([u8; 16]),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ListResponse {
+    pub workspace: String,
+    pub namespace: String,
+    pub count: usize,
+    pub keys: Vec<String>,
+    /// Values and metadata of the keys, if requested by the [`ListQuery`].
+    #[serde(default)]
+    pub items: Vec<ListItem>,
+    /// Groups of keys, if the [`ListQuery`] has a delimiter.
+    #[serde(default)]
+    pub common_prefixes: Vec<String>,
+    /// Cursor of the next page, `None` on the last one.
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Utf8,
+    Base64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ListItem {
+    pub key: String,
+    pub etag: Option<String>,
+    pub size: Option<u64>,
+    /// RFC 3339 timestamps.
+    pub created: Option<String>,
+    pub updated: Option<String>,
+    pub value: Option<String>,
+    pub encoding: Option<Encoding>,
+}
+
+impl ListItem {
+    /// The decoded value, `None` if values were not requested.
+    pub fn value(&self) -> Option<Vec<u8>> {
+        let value = self.value.as_ref()?;
+
+        match self.encoding {
+            Some(Encoding::Base64) => BASE64_STANDARD.decode(value).ok(),
+            _ => Some(value.as_bytes().to_vec()),
+        }
+    }
+
+    /// md5 of the value, `None` if metadata was not requested.
+    pub fn md5(&self) -> Option<[u8; 16]> {
+        <[u8; 16]>::from_hex(self.etag.as_ref()?.trim_matches('"')).ok()
+    }
+}
+
+#[derive(Serialize)]
+struct BatchGetRequest<'a> {
+    keys: &'a [String],
+}
+
+/// Result of [`HttpKeyValueStore::get_many`].
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct BatchGetResponse {
+    /// Found keys with values, ETags, sizes and timestamps.
+    pub items: Vec<ListItem>,
+    pub missing: Vec<String>,
+    /// Found keys beyond the payload limit of the server, [`HttpKeyValueStore::get_many`]
+    /// requests them again.
+    #[serde(default)]
+    pub remaining: Vec<String>,
+}
+
+/// Operation of [`HttpKeyValueStore::transaction`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    Put {
+        key: String,
+        value: Vec<u8>,
+        condition: Condition,
+    },
+    Delete {
+        key: String,
+        condition: Condition,
+    },
+    /// Only asserts `condition`, the key is left as is.
+    Check {
+        key: String,
+        condition: Condition,
+    },
+}
+
+/// Result of [`HttpKeyValueStore::transaction`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionResult {
+    /// md5 of each key after its operation, `None` if the key does not exist.
+    Committed(Vec<Option<[u8; 16]>>),
+    /// The condition of the operation at this index did not hold, nothing is applied.
+    Failed(usize),
+}
+
+#[derive(Serialize)]
+struct TransactionRequest<'a> {
+    operations: Vec<TransactionOperation<'a>>,
+}
+
+#[derive(Serialize)]
+struct TransactionOperation<'a> {
+    op: &'static str,
+    namespace: &'a str,
+    key: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encoding: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    if_match: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    if_none_match: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TransactionResponse {
+    results: Vec<TransactionStep>,
+}
+
+#[derive(Deserialize)]
+struct TransactionStep {
+    etag: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TransactionFailure {
+    index: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Order {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Page of a listing, see [`HttpKeyValueStore::list_page`].
+#[derive(Debug, Clone, Default)]
+pub struct ListQuery {
+    pub prefix: Option<String>,
+    /// Page size, every key if `None`.
+    pub limit: Option<u32>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+    pub order: Order,
+    /// Include values, a page is cut short once they exceed the payload limit of the server.
+    pub values: bool,
+    /// Include ETag, size and timestamps.
+    pub metadata: bool,
+    /// Keys containing the delimiter after the prefix are grouped into `common_prefixes`,
+    /// each counts as a single key of the page.
+    pub delimiter: Option<String>,
+}
+
+/// [`KeyValueStore`] backed by a namespace of a remote hulykvs server (API v2).
+///
+/// Requests are authenticated with a bearer JWT. Failed requests (transport errors,
+/// `429` and `5xx` responses) are retried with exponential backoff. Conditional writes
+/// are only retried if the server surely did not receive them, an unknown outcome is
+/// returned as an error.
+#[derive(Clone)]
+pub struct HttpKeyValueStore {
+    client: Client,
+    base: Url,
+    token: SecretString,
+    workspace: Uuid,
+    namespace: String,
+    retries: usize,
+    backoff: Duration,
+    conflict_retries: usize,
+}
+
+impl HttpKeyValueStore {
+    pub const DEFAULT_RETRIES: usize = 3;
+    pub const DEFAULT_BACKOFF: Duration = Duration::from_millis(100);
+    pub const DEFAULT_CONFLICT_RETRIES: usize = 16;
+    /// Keys per request of [`KeyValueStore::list`].
+    pub const LIST_PAGE_SIZE: u32 = 1000;
+    /// Keys per request of [`HttpKeyValueStore::get_many`], the limit of the server.
+    pub const BATCH_SIZE: usize = 1000;
+
+    /// `url` is the server endpoint, e.g. `http://localhost:8094`.
+    pub fn new(
+        url: &str,
+        token: SecretString,
+        workspace: Uuid,
+        namespace: impl Into<String>,
+    ) -> Result<Self> {
+        let base = Url::parse(url).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+
+        if base.cannot_be_a_base() {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid server url"));
+        }
+
+        Ok(HttpKeyValueStore {
+            client: Client::new(),
+            base,
+            token,
+            workspace,
+            namespace: namespace.into(),
+            retries: Self::DEFAULT_RETRIES,
+            backoff: Self::DEFAULT_BACKOFF,
+            conflict_retries: Self::DEFAULT_CONFLICT_RETRIES,
+        })
+    }
+
+    /// Same server and credentials, different namespace.
+    pub fn with_namespace(&self, namespace: impl Into<String>) -> Self {
+        HttpKeyValueStore {
+            namespace: namespace.into(),
+            ..self.clone()
+        }
+    }
+
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Retry failed requests `retries` times, waiting `backoff`, `2 * backoff`, ... in between.
+    pub fn with_retries(mut self, retries: usize, backoff: Duration) -> Self {
+        self.retries = retries;
+        self.backoff = backoff;
+        self
+    }
+
+    /// How many times [`KeyValueStore::update`] re-reads the value after a conflicting write.
+    pub fn with_conflict_retries(mut self, retries: usize) -> Self {
+        self.conflict_retries = retries;
+        self
+    }
+
+    pub fn workspace(&self) -> Uuid {
+        self.workspace
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Stores `value` if `condition` holds. Resolves to the md5 of the stored value,
+    /// or `None` if the server rejected the precondition.
+    pub async fn put<K: AsRef<[u8]>, V: Into<Value>>(
+        &self,
+        key: K,
+        value: V,
+        condition: Condition,
+    ) -> Result<Option<[u8; 16]>> {
+        let url = self.url(Some(utf8(key.as_ref())?))?;
+        let value = value.into();
+
+        let replay = condition == Condition::None;
+
+        let response = self
+            .send_with(replay, || {
+                let request = self.client.put(url.clone()).body(value.bytes.clone());
+
+                match condition {
+                    Condition::None => request,
+                    Condition::Exists => request.header(IF_MATCH, "*"),
+                    Condition::Absent => request.header(IF_NONE_MATCH, "*"),
+                    Condition::Matches(md5) => request.header(IF_MATCH, format_etag(md5)),
+                    Condition::Differs(md5) => request.header(IF_NONE_MATCH, format_etag(md5)),
+                }
+            })
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(Some(
+                parse_etag(response.headers().get(ETAG))
+                    .unwrap_or_else(|| md5::compute(&value.bytes).0),
+            )),
+            StatusCode::PRECONDITION_FAILED => Ok(None),
+            _ => Err(status_error(response).await),
+        }
+    }
+
+    /// Removes `key`, resolves to `false` if it did not exist.
+    pub async fn delete<K: AsRef<[u8]>>(&self, key: K) -> Result<bool> {
+        let url = self.url(Some(utf8(key.as_ref())?))?;
+
+        let response = self.send(|| self.client.delete(url.clone())).await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            _ => Err(status_error(response).await),
+        }
+    }
+
+    /// Removes `key` if its current value has the given md5 (`If-Match`). Resolves to
+    /// `false` if the value has changed or the key does not exist.
+    pub async fn delete_if<K: AsRef<[u8]>>(&self, key: K, md5: [u8; 16]) -> Result<bool> {
+        let url = self.url(Some(utf8(key.as_ref())?))?;
+
+        let response = self
+            .send_with(false, || {
+                self.client
+                    .delete(url.clone())
+                    .header(IF_MATCH, format_etag(md5))
+            })
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            StatusCode::PRECONDITION_FAILED => Ok(false),
+            _ => Err(status_error(response).await),
+        }
+    }
+
+    pub async fn list_keys(&self, prefix: Option<&str>) -> Result<ListResponse> {
+        self.list_page(&ListQuery {
+            prefix: prefix.map(str::to_owned),
+            ..Default::default()
+        })
+        .await
+    }
+
+    pub async fn list_page(&self, query: &ListQuery) -> Result<ListResponse> {
+        let mut url = self.url(None)?;
+
+        {
+            let mut pairs = url.query_pairs_mut();
+
+            if let Some(prefix) = &query.prefix {
+                pairs.append_pair("prefix", prefix);
+            }
+
+            if let Some(limit) = query.limit {
+                pairs.append_pair("limit", &limit.to_string());
+            }
+
+            if let Some(cursor) = &query.cursor {
+                pairs.append_pair("cursor", cursor);
+            }
+
+            if query.order == Order::Desc {
+                pairs.append_pair("order", "desc");
+            }
+
+            if query.values {
+                pairs.append_pair("values", "base64");
+            }
+
+            if query.metadata {
+                pairs.append_pair("metadata", "true");
+            }
+
+            if let Some(delimiter) = &query.delimiter {
+                pairs.append_pair("delimiter", delimiter);
+            }
+        }
+
+        let response = self.send(|| self.client.get(url.clone())).await?;
+
+        if !response.status().is_success() {
+            return Err(status_error(response).await);
+        }
+
+        response.json().await.map_err(Error::other)
+    }
+
+    /// Values of `keys` in a single request, unless they exceed the payload limit
+    /// of the server or [`Self::BATCH_SIZE`].
+    pub async fn get_many<K: AsRef<str>>(&self, keys: &[K]) -> Result<BatchGetResponse> {
+        let mut url = self.url(None)?;
+        url.set_path(&format!("{}:batchGet", url.path()));
+
+        let mut result = BatchGetResponse::default();
+
+        for chunk in keys.chunks(Self::BATCH_SIZE) {
+            let mut keys: Vec<String> = chunk.iter().map(|key| key.as_ref().to_owned()).collect();
+
+            loop {
+                let body = BatchGetRequest { keys: &keys };
+
+                let response = self
+                    .send(|| self.client.post(url.clone()).json(&body))
+                    .await?;
+
+                if !response.status().is_success() {
+                    return Err(status_error(response).await);
+                }
+
+                let response: BatchGetResponse = response.json().await.map_err(Error::other)?;
+
+                result.items.extend(response.items);
+                result.missing.extend(response.missing);
+
+                if response.remaining.is_empty() {
+                    break;
+                }
+
+                keys = response.remaining;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Applies `operations` atomically within the namespace of the store, up to
+    /// [`Self::BATCH_SIZE`] of them. A transaction whose response got lost is reported
+    /// as an error rather than resubmitted, as it may have been committed.
+    pub async fn transaction(&self, operations: &[Operation]) -> Result<TransactionResult> {
+        let mut url = self.base.clone();
+        let workspace = format!("{}:transaction", self.workspace);
+
+        url.path_segments_mut()
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "invalid server url"))?
+            .pop_if_empty()
+            .extend(["api2", &workspace]);
+
+        let operations = operations
+            .iter()
+            .map(|operation| {
+                let (op, key, value, condition) = match operation {
+                    Operation::Put {
+                        key,
+                        value,
+                        condition,
+                    } => ("put", key, Some(value), condition),
+                    Operation::Delete { key, condition } => ("delete", key, None, condition),
+                    Operation::Check { key, condition } => ("check", key, None, condition),
+                };
+
+                let (if_match, if_none_match) = match *condition {
+                    Condition::None => (None, None),
+                    Condition::Exists => (Some("*".to_owned()), None),
+                    Condition::Absent => (None, Some("*".to_owned())),
+                    Condition::Matches(md5) => (Some(format_etag(md5)), None),
+                    Condition::Differs(md5) => (None, Some(format_etag(md5))),
+                };
+
+                TransactionOperation {
+                    op,
+                    namespace: &self.namespace,
+                    key,
+                    value: value.map(|value| BASE64_STANDARD.encode(value)),
+                    encoding: value.map(|_| "base64"),
+                    if_match,
+                    if_none_match,
+                }
+            })
+            .collect();
+
+        let body = TransactionRequest { operations };
+
+        let response = self
+            .send_with(false, || self.client.post(url.clone()).json(&body))
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => {
+                let response: TransactionResponse = response.json().await.map_err(Error::other)?;
+
+                Ok(TransactionResult::Committed(
+                    response
+                        .results
+                        .into_iter()
+                        .map(|step| step.etag.as_deref().and_then(parse_md5))
+                        .collect(),
+                ))
+            }
+
+            StatusCode::PRECONDITION_FAILED => {
+                let response: TransactionFailure = response.json().await.map_err(Error::other)?;
+                Ok(TransactionResult::Failed(response.index))
+            }
+
+            _ => Err(status_error(response).await),
+        }
+    }
+
+    fn url(&self, key: Option<&str>) -> Result<Url> {
+        let mut url = self.base.clone();
+        let workspace = self.workspace.to_string();
+
+        {
+            let mut segments = url
+                .path_segments_mut()
+                .map_err(|_| Error::new(ErrorKind::InvalidInput, "invalid server url"))?;
+
+            segments
+                .pop_if_empty()
+                .extend(["api2", &workspace, &self.namespace]);
+
+            if let Some(key) = key {
+                segments.push(key);
+            }
+        }
+
+        Ok(url)
+    }
+
+    async fn send(&self, request: impl Fn() -> RequestBuilder) -> Result<Response> {
+        self.send_with(true, request).await
+    }
+
+    /// Sends `request`, retrying failures. Unless `replay` is set, only failures where
+    /// the server surely did not apply the request (connect errors, `429`) are retried:
+    /// a conditional write that was applied but whose response got lost would fail its
+    /// own precondition when repeated.
+    async fn send_with(
+        &self,
+        replay: bool,
+        request: impl Fn() -> RequestBuilder,
+    ) -> Result<Response> {
+        let mut attempt = 0;
+
+        loop {
+            let result = request()
+                .bearer_auth(self.token.expose_secret())
+                .send()
+                .await;
+
+            let retryable = match &result {
+                Ok(response) => {
+                    (replay && response.status().is_server_error())
+                        || response.status() == StatusCode::TOO_MANY_REQUESTS
+                }
+                Err(error) => !error.is_builder() && (replay || error.is_connect()),
+            };
+
+            if !retryable || attempt >= self.retries {
+                return result.map_err(Error::other);
+            }
+
+            tokio::time::sleep(self.backoff * 2u32.saturating_pow(attempt as u32)).await;
+            attempt += 1;
+        }
+    }
+}
+
+impl KeyValueStore for HttpKeyValueStore {
+    async fn insert<K: Into<Key>, V: Into<Value>>(&self, key: K, value: V) -> Result<()> {
+        self.put(key.into(), value, Condition::None).await?;
+
+        Ok(())
+    }
+
+    async fn remove<K: Into<Key>>(&self, key: K) -> Result<bool> {
+        self.delete(key.into()).await
+    }
+
+    async fn exists<K: AsRef<[u8]>>(&self, key: K) -> Result<bool> {
+        let url = self.url(Some(utf8(key.as_ref())?))?;
+
+        let response = self.send(|| self.client.head(url.clone())).await?;
+
+        match response.status() {
+            StatusCode::OK => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            _ => Err(status_error(response).await),
+        }
+    }
+
+    async fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Entry>> {
+        let url = self.url(Some(utf8(key.as_ref())?))?;
+
+        let response = self.send(|| self.client.get(url.clone())).await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let md5 = parse_etag(response.headers().get(ETAG));
+                let value = Value::from(response.bytes().await.map_err(Error::other)?.to_vec());
+
+                Ok(Some(Entry {
+                    key: Key::new(key.as_ref()),
+                    md5: Some(md5.unwrap_or_else(|| md5::compute(&value.bytes).0)),
+                    value,
+                }))
+            }
+            StatusCode::NOT_FOUND => Ok(None),
+            _ => Err(status_error(response).await),
+        }
+    }
+
+    async fn list<K: AsRef<[u8]>>(&self, prefix: K) -> Result<impl Iterator<Item = Key>> {
+        let prefix = utf8(prefix.as_ref())?;
+        let prefix = (!prefix.is_empty()).then_some(prefix);
+
+        let mut query = ListQuery {
+            prefix: prefix.map(str::to_owned),
+            limit: Some(Self::LIST_PAGE_SIZE),
+            ..Default::default()
+        };
+
+        let mut keys = Vec::new();
+
+        loop {
+            let page = self.list_page(&query).await?;
+
+            keys.extend(page.keys.into_iter().map(|key| Key::from(key.into_bytes())));
+
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+
+        Ok(keys.into_iter())
+    }
+
+    async fn update<K, F>(&self, key: K, mut f: F) -> Result<Option<Value>>
+    where
+        K: Into<Key>,
+        F: FnMut(Option<&Value>) -> Result<Option<Value>>,
+    {
+        let key = key.into();
+
+        for _ in 0..=self.conflict_retries {
+            let current = self.get(&key).await?;
+
+            let condition = match &current {
+                Some(entry) => Condition::Matches(entry.md5.unwrap_or_default()),
+                None => Condition::Absent,
+            };
+
+            match f(current.as_ref().map(|entry| &entry.value))? {
+                Some(value) => {
+                    if self.put(&key, value.clone(), condition).await?.is_some() {
+                        return Ok(Some(value));
+                    }
+                }
+                None => match &current {
+                    Some(entry) => {
+                        if self.delete_if(&key, entry.md5.unwrap_or_default()).await? {
+                            return Ok(None);
+                        }
+                    }
+                    None => return Ok(None),
+                },
+            }
+        }
+
+        Err(Error::other("update conflict, retries exhausted"))
+    }
+}
+
+fn utf8(key: &[u8]) -> Result<&str> {
+    std::str::from_utf8(key).map_err(|e| Error::new(ErrorKind::InvalidInput, e))
+}
+
+/// Accepts both bare and quoted (optionally weak) md5 entity tags.
+fn parse_etag(value: Option<&HeaderValue>) -> Option<[u8; 16]> {
+    parse_md5(value?.to_str().ok()?)
+}
+
+fn parse_md5(etag: &str) -> Option<[u8; 16]> {
+    let value = etag.trim();
+    let value = value.strip_prefix("W/").unwrap_or(value);
+
+    <[u8; 16]>::from_hex(value.trim_matches('"')).ok()
+}
+
+fn format_etag(md5: [u8; 16]) -> String {
+    format!("\"{}\"", hex::encode(md5))
+}
+
+async fn status_error(response: Response) -> Error {
+    let status = response.status();
+
+    let kind = match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ErrorKind::PermissionDenied,
+        StatusCode::BAD_REQUEST => ErrorKind::InvalidInput,
+        _ => ErrorKind::Other,
+    };
+
+    let body = response.text().await.unwrap_or_default();
+
+    Error::new(kind, format!("{status}: {body}"))
+}
@@ -0,0 +1,291 @@
+//
+// Copyright © 2025 Hardcore Engineering Inc.
+//
+// Licensed under the Eclipse Public License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License. You may
+// obtain a copy of the License at https://www.eclipse.org/legal/epl-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// Retries against a stub server holding a single value, which applies writes
+// and transactions and then drops the connection instead of responding.
+
+use std::{
+    io::Result,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use hulykvs::KeyValueStore;
+use hulykvs_client::{Condition, HttpKeyValueStore, Operation};
+use secrecy::SecretString;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use uuid::Uuid;
+
+#[derive(Default)]
+struct State {
+    value: Option<Vec<u8>>,
+    /// Responses to writes still to be lost.
+    lose: usize,
+    /// Methods of the received requests.
+    requests: Vec<String>,
+}
+
+struct Request {
+    method: String,
+    if_match: Option<String>,
+    if_none_match: Option<String>,
+    body: Vec<u8>,
+}
+
+impl State {
+    fn writes(&self) -> usize {
+        self.requests
+            .iter()
+            .filter(|method| *method != "GET")
+            .count()
+    }
+
+    /// `None` drops the connection.
+    fn respond(&mut self, request: Request) -> Option<Vec<u8>> {
+        self.requests.push(request.method.clone());
+
+        let etag = self
+            .value
+            .as_ref()
+            .map(|value| format!("\"{:x}\"", md5::compute(value)));
+
+        if request.method == "POST" {
+            if self.lose > 0 {
+                self.lose -= 1;
+                return None;
+            }
+
+            return Some(response("200 OK", None, br#"{"results":[]}"#));
+        }
+
+        if request.method == "GET" {
+            return Some(match (&self.value, &etag) {
+                (Some(value), Some(etag)) => response("200 OK", Some(etag), value),
+                _ => response("404 Not Found", None, b""),
+            });
+        }
+
+        let matches = match (&request.if_match, &etag) {
+            (Some(condition), Some(etag)) => condition == "*" || condition == etag,
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+
+        let absent = request.if_none_match.is_none() || self.value.is_none();
+
+        if !matches || !absent {
+            return Some(response("412 Precondition Failed", None, b""));
+        }
+
+        self.value = (request.method == "PUT").then_some(request.body);
+
+        if self.lose > 0 {
+            self.lose -= 1;
+            return None;
+        }
+
+        Some(response("204 No Content", None, b""))
+    }
+}
+
+fn response(status: &str, etag: Option<&String>, body: &[u8]) -> Vec<u8> {
+    let mut head = format!(
+        "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        body.len()
+    );
+
+    if let Some(etag) = etag {
+        head.push_str(&format!("ETag: {etag}\r\n"));
+    }
+
+    head.push_str("\r\n");
+
+    [head.as_bytes(), body].concat()
+}
+
+async fn read_request(socket: &mut TcpStream) -> Result<Option<Request>> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 4096];
+
+    let head_end = loop {
+        if let Some(index) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break index + 4;
+        }
+
+        let read = socket.read(&mut chunk).await?;
+
+        if read == 0 {
+            return Ok(None);
+        }
+
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..head_end]).into_owned();
+    let mut lines = head.lines();
+
+    let method = lines
+        .next()
+        .and_then(|line| line.split(' ').next())
+        .unwrap_or_default()
+        .to_owned();
+
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_owned()))
+        .collect();
+
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.clone())
+    };
+
+    let length: usize = header("content-length").map_or(0, |value| value.parse().unwrap());
+    let mut body = buffer[head_end..].to_vec();
+
+    while body.len() < length {
+        let read = socket.read(&mut chunk).await?;
+
+        if read == 0 {
+            return Ok(None);
+        }
+
+        body.extend_from_slice(&chunk[..read]);
+    }
+
+    Ok(Some(Request {
+        method,
+        if_match: header("if-match"),
+        if_none_match: header("if-none-match"),
+        body,
+    }))
+}
+
+async fn stub(state: State) -> Result<(HttpKeyValueStore, Arc<Mutex<State>>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}", listener.local_addr()?);
+    let state = Arc::new(Mutex::new(state));
+
+    let shared = state.clone();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let state = shared.clone();
+
+            tokio::spawn(async move {
+                if let Ok(Some(request)) = read_request(&mut socket).await {
+                    let response = state.lock().unwrap().respond(request);
+
+                    if let Some(response) = response {
+                        let _ = socket.write_all(&response).await;
+                    }
+                }
+            });
+        }
+    });
+
+    let store =
+        HttpKeyValueStore::new(&url, SecretString::from("token"), Uuid::nil(), "namespace")?
+            .with_retries(3, Duration::from_millis(1));
+
+    Ok((store, state))
+}
+
+#[tokio::test]
+async fn lost_conditional_write_is_not_replayed() -> Result<()> {
+    let (store, state) = stub(State {
+        value: Some(b"1".to_vec()),
+        lose: 1,
+        ..Default::default()
+    })
+    .await?;
+
+    // applied once, the retry would fail its If-Match and count again
+    assert!(store.increment(b"counter".to_vec(), 1).await.is_err());
+
+    let state = state.lock().unwrap();
+    assert_eq!(state.value.as_deref(), Some(&b"2"[..]));
+    assert_eq!(state.writes(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn lost_conditional_delete_is_not_replayed() -> Result<()> {
+    let (store, state) = stub(State {
+        value: Some(b"1".to_vec()),
+        lose: 1,
+        ..Default::default()
+    })
+    .await?;
+
+    assert!(
+        store
+            .delete_if("counter", md5::compute(b"1").0)
+            .await
+            .is_err()
+    );
+
+    let state = state.lock().unwrap();
+    assert_eq!(state.value, None);
+    assert_eq!(state.writes(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn lost_unconditional_write_is_retried() -> Result<()> {
+    let (store, state) = stub(State {
+        lose: 1,
+        ..Default::default()
+    })
+    .await?;
+
+    assert!(
+        store
+            .put("counter", b"1".to_vec(), Condition::None)
+            .await?
+            .is_some()
+    );
+
+    let state = state.lock().unwrap();
+    assert_eq!(state.value.as_deref(), Some(&b"1"[..]));
+    assert_eq!(state.writes(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn lost_transaction_is_not_replayed() -> Result<()> {
+    let (store, state) = stub(State {
+        lose: 1,
+        ..Default::default()
+    })
+    .await?;
+
+    let operations = [Operation::Check {
+        key: "counter".to_owned(),
+        condition: Condition::Absent,
+    }];
+
+    assert!(store.transaction(&operations).await.is_err());
+    assert_eq!(state.lock().unwrap().writes(), 1);
+
+    Ok(())
+}
@@ -0,0 +1,267 @@
+//
+// Copyright © 2025 Hardcore Engineering Inc.
+//
+// Licensed under the Eclipse Public License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License. You may
+// obtain a copy of the License at https://www.eclipse.org/legal/epl-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+// These tests run against a local hulykvs instance:
+//   HULYKVS_URL (default http://localhost:8094)
+//   HULY_TOKEN_SECRET (default "secret")
+//
+//   cargo test -p hulykvs_client -- --ignored
+
+use std::io::Result;
+
+use hulykvs::KeyValueStore;
+use hulykvs_client::{
+    Condition, HttpKeyValueStore, ListQuery, Operation, Order, TransactionResult,
+};
+use jsonwebtoken::{EncodingKey, Header};
+use secrecy::SecretString;
+use uuid::Uuid;
+
+const WORKSPACE: &str = "00000000-0000-0000-0000-000000000001";
+
+fn store(namespace: &str) -> HttpKeyValueStore {
+    let url = std::env::var("HULYKVS_URL").unwrap_or("http://localhost:8094".to_owned());
+    let secret = std::env::var("HULY_TOKEN_SECRET").unwrap_or("secret".to_owned());
+
+    let claims = serde_json::json!({
+        "account": "aaaaaaaa-bbbb-cccc-dddd-eeeeeeeeeeee",
+        "workspace": WORKSPACE,
+        "extra": { "service": "account" }
+    });
+
+    let token = jsonwebtoken::encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+    .unwrap();
+
+    HttpKeyValueStore::new(
+        &url,
+        SecretString::from(token),
+        Uuid::parse_str(WORKSPACE).unwrap(),
+        namespace,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+#[ignore = "requires a running hulykvs server"]
+async fn insert_get_remove() -> Result<()> {
+    let store = store("client_tests");
+    let key = "insert get/remove";
+
+    store
+        .insert(key.as_bytes().to_vec(), b"value".to_vec())
+        .await?;
+
+    let entry = store.get(key).await?.expect("key exists");
+    assert_eq!(entry.value.bytes, b"value");
+    assert_eq!(entry.md5, Some(md5::compute(b"value").0));
+    assert!(store.exists(key).await?);
+
+    assert!(store.remove(key.as_bytes().to_vec()).await?);
+    assert!(!store.remove(key.as_bytes().to_vec()).await?);
+    assert!(store.get(key).await?.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "requires a running hulykvs server"]
+async fn conditional_put() -> Result<()> {
+    let store = store("client_tests");
+    let key = "conditional";
+
+    store.delete(key).await?;
+
+    assert!(
+        store
+            .put(key, b"a".to_vec(), Condition::Exists)
+            .await?
+            .is_none()
+    );
+
+    let md5 = store.put(key, b"a".to_vec(), Condition::Absent).await?;
+    assert_eq!(md5, Some(md5::compute(b"a").0));
+    assert!(
+        store
+            .put(key, b"b".to_vec(), Condition::Absent)
+            .await?
+            .is_none()
+    );
+
+    let stale = md5::compute(b"stale").0;
+    assert!(
+        store
+            .put(key, b"b".to_vec(), Condition::Matches(stale))
+            .await?
+            .is_none()
+    );
+    assert!(
+        store
+            .put(key, b"b".to_vec(), Condition::Matches(md5.unwrap()))
+            .await?
+            .is_some()
+    );
+
+    assert!(!store.delete_if(key, stale).await?);
+    assert!(store.delete_if(key, md5::compute(b"b").0).await?);
+    assert!(!store.delete_if(key, md5::compute(b"b").0).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "requires a running hulykvs server"]
+async fn list_and_counters() -> Result<()> {
+    let store = store("client_tests_list");
+
+    for key in store.list("").await?.collect::<Vec<_>>() {
+        store.remove(key).await?;
+    }
+
+    for key in ["a/1", "a/2", "b/1"] {
+        store.insert(key.as_bytes().to_vec(), b"x".to_vec()).await?;
+    }
+
+    assert_eq!(store.list("a/").await?.count(), 2);
+    assert_eq!(store.list_keys(None).await?.count, 3);
+
+    let mut query = ListQuery {
+        limit: Some(2),
+        ..Default::default()
+    };
+
+    let page = store.list_page(&query).await?;
+    assert_eq!(page.keys, ["a/1", "a/2"]);
+
+    query.cursor = page.next_cursor;
+    let page = store.list_page(&query).await?;
+    assert_eq!(page.keys, ["b/1"]);
+    assert_eq!(page.next_cursor, None);
+
+    query.cursor = None;
+    query.order = Order::Desc;
+    assert_eq!(store.list_page(&query).await?.keys, ["b/1", "a/2"]);
+
+    let query = ListQuery {
+        prefix: Some("a/".to_owned()),
+        values: true,
+        metadata: true,
+        ..Default::default()
+    };
+
+    let page = store.list_page(&query).await?;
+    assert_eq!(page.items.len(), 2);
+    assert_eq!(page.items[0].value(), Some(b"x".to_vec()));
+    assert_eq!(page.items[0].md5(), Some(md5::compute(b"x").0));
+    assert_eq!(page.items[0].size, Some(1));
+
+    let mut query = ListQuery {
+        delimiter: Some("/".to_owned()),
+        limit: Some(1),
+        ..Default::default()
+    };
+
+    let page = store.list_page(&query).await?;
+    assert_eq!(page.common_prefixes, ["a/"]);
+    assert!(page.keys.is_empty());
+
+    query.cursor = page.next_cursor;
+    let page = store.list_page(&query).await?;
+    assert_eq!(page.common_prefixes, ["b/"]);
+    assert_eq!(page.next_cursor, None);
+
+    let batch = store.get_many(&["b/1", "a/1", "c/1"]).await?;
+    let keys = batch.items.iter().map(|item| item.key.as_str());
+    assert_eq!(keys.collect::<Vec<_>>(), ["a/1", "b/1"]);
+    assert_eq!(batch.items[0].value(), Some(b"x".to_vec()));
+    assert_eq!(batch.missing, ["c/1"]);
+
+    let counter = b"counter".to_vec();
+    store.remove(counter.clone()).await?;
+
+    let tasks: Vec<_> = (0..8)
+        .map(|_| {
+            let store = store.clone();
+            let counter = counter.clone();
+            tokio::spawn(async move { store.increment(counter, 1).await })
+        })
+        .collect();
+
+    for task in tasks {
+        task.await.unwrap()?;
+    }
+
+    assert_eq!(store.decrement(counter, 3).await?, 5);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "requires a running hulykvs server"]
+async fn transaction() -> Result<()> {
+    let store = store("client_tests_transaction");
+
+    store.delete("a").await?;
+    store.delete("b").await?;
+
+    let put = |key: &str, value: &[u8], condition| Operation::Put {
+        key: key.to_owned(),
+        value: value.to_vec(),
+        condition,
+    };
+
+    let result = store
+        .transaction(&[
+            put("a", b"1", Condition::Absent),
+            put("b", b"2", Condition::Absent),
+        ])
+        .await?;
+    assert_eq!(
+        result,
+        TransactionResult::Committed(vec![Some(md5::compute(b"1").0), Some(md5::compute(b"2").0)])
+    );
+
+    let result = store
+        .transaction(&[
+            put("a", b"3", Condition::Matches(md5::compute(b"1").0)),
+            put("b", b"4", Condition::Absent),
+        ])
+        .await?;
+    assert_eq!(result, TransactionResult::Failed(1));
+    assert_eq!(store.get("a").await?.unwrap().value.bytes, b"1");
+
+    let result = store
+        .transaction(&[
+            Operation::Check {
+                key: "a".to_owned(),
+                condition: Condition::Matches(md5::compute(b"1").0),
+            },
+            Operation::Delete {
+                key: "b".to_owned(),
+                condition: Condition::Exists,
+            },
+        ])
+        .await?;
+    assert_eq!(
+        result,
+        TransactionResult::Committed(vec![Some(md5::compute(b"1").0), None])
+    );
+    assert!(store.get("b").await?.is_none());
+
+    Ok(())
+}
@@ -0,0 +1 @@
+ALTER TABLE kvs ADD COLUMN size INT8 AS (octet_length(value)) STORED;
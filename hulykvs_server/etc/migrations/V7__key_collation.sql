@@ -0,0 +1,2 @@
+-- no-op for CockroachDB, strings already compare bytewise; kept to preserve migration numbering parity with PostgreSQL.
+SELECT 1;
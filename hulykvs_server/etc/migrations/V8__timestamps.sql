@@ -0,0 +1,2 @@
+-- rows existing before the migration get the time of the migration
+ALTER TABLE kvs ADD COLUMN created TIMESTAMPTZ NOT NULL DEFAULT now(), ADD COLUMN updated TIMESTAMPTZ NOT NULL DEFAULT now();
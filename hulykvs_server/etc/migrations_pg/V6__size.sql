@@ -0,0 +1 @@
+ALTER TABLE kvs ADD COLUMN IF NOT EXISTS size bigint GENERATED ALWAYS AS (octet_length(value)) STORED;
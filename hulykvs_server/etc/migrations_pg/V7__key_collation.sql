@@ -0,0 +1,2 @@
+-- keys compare bytewise, like on CockroachDB: literal prefix ranges and keyset pagination use the primary key
+ALTER TABLE kvs ALTER COLUMN key TYPE text COLLATE "C";
@@ -0,0 +1,2 @@
+-- rows existing before the migration get the time of the migration
+ALTER TABLE kvs ADD COLUMN IF NOT EXISTS created timestamptz NOT NULL DEFAULT now(), ADD COLUMN IF NOT EXISTS updated timestamptz NOT NULL DEFAULT now();
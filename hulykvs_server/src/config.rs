@@ -13,7 +13,10 @@
 // limitations under the License.
 //
 
-use std::{path::Path, sync::LazyLock};
+use std::{
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
 
 use config::FileFormat;
 use serde::Deserialize;
@@ -25,14 +28,39 @@ pub struct Config {
     pub bind_port: u16,
     pub bind_host: String,
 
+    /// PEM certificate chain and private key, TLS is served on `bind_port` if both are set
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+
+    /// Additional plain HTTP listener when TLS is enabled
+    pub http_port: Option<u16>,
+    /// Redirect plain HTTP requests to HTTPS instead of serving them
+    pub http_redirect: bool,
+
     pub token_secret: String,
 
+    /// Namespace access rules, see `policy.rs`
+    pub policy_file: Option<PathBuf>,
+
     pub db_connection: String,
     pub db_scheme: String,
 
+    /// CA bundle, client certificate and key (PEM) for TLS to the database,
+    /// override `sslrootcert`, `sslcert` and `sslkey` of the connection string
+    pub db_ssl_root_cert: Option<PathBuf>,
+    pub db_ssl_cert: Option<PathBuf>,
+    pub db_ssl_key: Option<PathBuf>,
+
     pub payload_size_limit: size::Size,
 
     pub default_workspace_uuid: Uuid,
+
+    /// OTLP/HTTP collector (e.g. `http://localhost:4318`), spans are exported if set
+    pub otel_endpoint: Option<String>,
+    pub otel_service_name: String,
+
+    /// Number of workspaces broken down in metrics, the rest is reported as `other`
+    pub metrics_workspaces: usize,
 }
 
 pub static CONFIG: LazyLock<Config> = LazyLock::new(|| {
@@ -0,0 +1,498 @@
+//
+// Copyright © 2025 Hardcore Engineering Inc.
+//
+// Licensed under the Eclipse Public License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License. You may
+// obtain a copy of the License at https://www.eclipse.org/legal/epl-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! TLS to the database with libpq `sslmode` semantics.
+//!
+//! tokio-postgres only knows `disable`, `prefer` and `require`, so `sslmode`,
+//! `sslrootcert`, `sslcert` and `sslkey` are taken out of the connection string
+//! and applied to the rustls configuration instead:
+//!
+//! - `disable`: plain connection
+//! - `prefer` (default), `require`: encrypted, the certificate is verified against
+//!   the CA bundle if one is given (like libpq does when a root certificate exists)
+//! - `verify-ca`: the certificate must be issued by a trusted CA
+//! - `verify-full`: as `verify-ca`, and the certificate must match the host name
+//!
+//! Without a CA bundle the Mozilla root certificates are trusted.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use rustls::{
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    client::{
+        WebPkiServerVerifier,
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    },
+    crypto::{CryptoProvider, ring, verify_tls12_signature, verify_tls13_signature},
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime, pem::PemObject},
+};
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+use crate::config::CONFIG;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SslMode {
+    Disable,
+    Prefer,
+    Require,
+    VerifyCa,
+    VerifyFull,
+}
+
+impl SslMode {
+    fn parse(value: &str) -> anyhow::Result<Self> {
+        Ok(match value {
+            "disable" => SslMode::Disable,
+            "prefer" => SslMode::Prefer,
+            "require" => SslMode::Require,
+            "verify-ca" => SslMode::VerifyCa,
+            "verify-full" => SslMode::VerifyFull,
+            // allow and the like are not supported by tokio-postgres
+            _ => anyhow::bail!("unsupported sslmode: {value}"),
+        })
+    }
+
+    /// Mode understood by tokio-postgres.
+    fn as_postgres(&self) -> &'static str {
+        match self {
+            SslMode::Disable => "disable",
+            SslMode::Prefer => "prefer",
+            _ => "require",
+        }
+    }
+}
+
+/// Connection string with TLS settings taken out.
+#[derive(Debug)]
+pub struct ConnectionString {
+    pub connection: String,
+    pub ssl_mode: SslMode,
+    pub root_cert: Option<PathBuf>,
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+}
+
+impl ConnectionString {
+    /// Accepts both URL (`postgresql://...?sslmode=...`) and key/value
+    /// (`host=... sslmode=...`) connection strings.
+    pub fn parse(connection: &str) -> anyhow::Result<Self> {
+        let mut ssl_mode = SslMode::Prefer;
+        let mut root_cert = None;
+        let mut cert = None;
+        let mut key = None;
+
+        let mut take = |name: &str, value: String| -> anyhow::Result<bool> {
+            match name {
+                "sslmode" => ssl_mode = SslMode::parse(&value)?,
+                "sslrootcert" => root_cert = Some(PathBuf::from(value)),
+                "sslcert" => cert = Some(PathBuf::from(value)),
+                "sslkey" => key = Some(PathBuf::from(value)),
+                _ => return Ok(false),
+            }
+
+            Ok(true)
+        };
+
+        let is_url =
+            connection.starts_with("postgres://") || connection.starts_with("postgresql://");
+
+        let mut rest = Vec::new();
+
+        let base = if is_url {
+            let (base, query) = connection.split_once('?').unwrap_or((connection, ""));
+
+            for param in query.split('&').filter(|p| !p.is_empty()) {
+                let (k, v) = param.split_once('=').unwrap_or((param, ""));
+
+                if !take(k, percent_decode(v)?)? {
+                    rest.push(param.to_owned());
+                }
+            }
+
+            Some(base)
+        } else {
+            for (k, v) in key_values(connection)? {
+                if !take(&k, v.clone())? {
+                    rest.push(format!("{k}={}", quote(&v)));
+                }
+            }
+
+            None
+        };
+
+        rest.push(format!("sslmode={}", ssl_mode.as_postgres()));
+
+        let connection = match base {
+            Some(base) => format!("{base}?{}", rest.join("&")),
+            None => rest.join(" "),
+        };
+
+        Ok(ConnectionString {
+            connection,
+            ssl_mode,
+            root_cert,
+            cert,
+            key,
+        })
+    }
+}
+
+/// Splits a libpq key/value connection string: `key = value` pairs separated by
+/// whitespace, values may be single-quoted, `\'` and `\\` escape within values.
+fn key_values(connection: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let mut params = Vec::new();
+    let mut chars = connection.chars().peekable();
+
+    let skip_whitespace = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    };
+
+    loop {
+        skip_whitespace(&mut chars);
+
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=') {
+            key.push(c);
+        }
+
+        skip_whitespace(&mut chars);
+
+        if key.is_empty() || chars.next() != Some('=') {
+            anyhow::bail!("invalid connection string, expected key=value near {key:?}");
+        }
+
+        skip_whitespace(&mut chars);
+
+        let mut value = String::new();
+
+        if chars.next_if_eq(&'\'').is_some() {
+            loop {
+                match chars.next() {
+                    Some('\'') => break,
+                    Some('\\') => value.push(chars.next().context("unterminated escape")?),
+                    Some(c) => value.push(c),
+                    None => anyhow::bail!("unterminated quoted value of {key}"),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                match c {
+                    '\\' => value.push(chars.next().context("unterminated escape")?),
+                    c => value.push(c),
+                }
+            }
+        }
+
+        params.push((key, value));
+    }
+
+    Ok(params)
+}
+
+/// Value of a key/value connection string, quoted if needed.
+fn quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || c == '\'' || c == '\\');
+
+    match plain {
+        true => value.to_owned(),
+        false => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")),
+    }
+}
+
+fn percent_decode(value: &str) -> anyhow::Result<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut input = value.bytes();
+
+    while let Some(b) = input.next() {
+        if b == b'%' {
+            let hex = [input.next(), input.next()];
+            let [Some(hi), Some(lo)] = hex else {
+                anyhow::bail!("invalid percent encoding: {value}");
+            };
+            bytes.push(
+                u8::from_str_radix(std::str::from_utf8(&[hi, lo])?, 16)
+                    .with_context(|| format!("invalid percent encoding: {value}"))?,
+            );
+        } else {
+            bytes.push(b);
+        }
+    }
+
+    Ok(String::from_utf8(bytes)?)
+}
+
+/// Connection string for tokio-postgres and the matching TLS connector.
+/// `db_ssl_*` settings from [`CONFIG`] override those of the connection string.
+pub fn connector(connection: &str) -> anyhow::Result<(String, MakeRustlsConnect)> {
+    let parsed = ConnectionString::parse(connection)?;
+
+    let root_cert = CONFIG.db_ssl_root_cert.clone().or(parsed.root_cert);
+    let cert = CONFIG.db_ssl_cert.clone().or(parsed.cert);
+    let key = CONFIG.db_ssl_key.clone().or(parsed.key);
+
+    let provider = Arc::new(ring::default_provider());
+
+    let verify = match parsed.ssl_mode {
+        SslMode::VerifyFull => Verify::Full,
+        SslMode::VerifyCa => Verify::Ca,
+        _ if root_cert.is_some() => Verify::Ca,
+        _ => Verify::None,
+    };
+
+    let roots = match &root_cert {
+        Some(path) => load_roots(path)?,
+        None => RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    };
+
+    let verifier = Verifier {
+        webpki: match verify {
+            Verify::None => None,
+            _ => Some(
+                WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                    .build()?,
+            ),
+        },
+        verify,
+        provider: provider.clone(),
+    };
+
+    let builder = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier));
+
+    let config = match (cert, key) {
+        (Some(cert), Some(key)) => {
+            let chain = CertificateDer::pem_file_iter(&cert)
+                .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                .with_context(|| format!("cannot read certificates from {}", cert.display()))?;
+
+            let key = PrivateKeyDer::from_pem_file(&key)
+                .with_context(|| format!("cannot read private key from {}", key.display()))?;
+
+            builder.with_client_auth_cert(chain, key)?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        _ => anyhow::bail!("both db_ssl_cert and db_ssl_key must be set"),
+    };
+
+    Ok((parsed.connection, MakeRustlsConnect::new(config)))
+}
+
+fn load_roots(path: &Path) -> anyhow::Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+
+    for cert in CertificateDer::pem_file_iter(path)
+        .with_context(|| format!("cannot read CA bundle {}", path.display()))?
+    {
+        roots.add(cert?)?;
+    }
+
+    Ok(roots)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verify {
+    None,
+    Ca,
+    Full,
+}
+
+#[derive(Debug)]
+struct Verifier {
+    webpki: Option<Arc<WebPkiServerVerifier>>,
+    verify: Verify,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for Verifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let Some(webpki) = &self.webpki else {
+            return Ok(ServerCertVerified::assertion());
+        };
+
+        match webpki.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+        {
+            Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::NotValidForName
+                | rustls::CertificateError::NotValidForNameContext { .. },
+            )) if self.verify == Verify::Ca => Ok(ServerCertVerified::assertion()),
+            result => result,
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(connection: &str) -> ConnectionString {
+        ConnectionString::parse(connection).unwrap()
+    }
+
+    #[test]
+    fn url_modes() {
+        for (mode, expected, postgres) in [
+            ("disable", SslMode::Disable, "disable"),
+            ("prefer", SslMode::Prefer, "prefer"),
+            ("require", SslMode::Require, "require"),
+            ("verify-ca", SslMode::VerifyCa, "require"),
+            ("verify-full", SslMode::VerifyFull, "require"),
+        ] {
+            let parsed = parse(&format!("postgresql://root@db:26257/kvs?sslmode={mode}"));
+
+            assert_eq!(parsed.ssl_mode, expected);
+            assert_eq!(
+                parsed.connection,
+                format!("postgresql://root@db:26257/kvs?sslmode={postgres}")
+            );
+        }
+
+        let parsed = parse("postgres://root@db/kvs");
+        assert_eq!(parsed.ssl_mode, SslMode::Prefer);
+        assert_eq!(parsed.connection, "postgres://root@db/kvs?sslmode=prefer");
+
+        assert!(ConnectionString::parse("postgres://db/kvs?sslmode=allow").is_err());
+    }
+
+    #[test]
+    fn url_paths() {
+        let parsed = parse(
+            "postgresql://db/kvs?application_name=kvs&sslrootcert=%2Fpath%20with%20space%2Fca.pem\
+             &sslmode=verify-full&sslcert=/certs/client.crt&sslkey=/certs/client.key&connect_timeout=5",
+        );
+
+        assert_eq!(parsed.ssl_mode, SslMode::VerifyFull);
+        assert_eq!(
+            parsed.root_cert,
+            Some(PathBuf::from("/path with space/ca.pem"))
+        );
+        assert_eq!(parsed.cert, Some(PathBuf::from("/certs/client.crt")));
+        assert_eq!(parsed.key, Some(PathBuf::from("/certs/client.key")));
+        assert_eq!(
+            parsed.connection,
+            "postgresql://db/kvs?application_name=kvs&connect_timeout=5&sslmode=require"
+        );
+    }
+
+    #[test]
+    fn key_value_modes() {
+        for (mode, expected) in [
+            ("disable", SslMode::Disable),
+            ("prefer", SslMode::Prefer),
+            ("require", SslMode::Require),
+            ("verify-ca", SslMode::VerifyCa),
+            ("verify-full", SslMode::VerifyFull),
+        ] {
+            let parsed = parse(&format!("host=db sslmode = '{mode}'"));
+
+            assert_eq!(parsed.ssl_mode, expected);
+            assert_eq!(
+                parsed.connection,
+                format!("host=db sslmode={}", expected.as_postgres())
+            );
+        }
+
+        assert!(ConnectionString::parse("host=db sslmode=allow").is_err());
+    }
+
+    #[test]
+    fn key_value_quotes() {
+        let parsed = parse(
+            r"host=db user=root password='a b\'c\\d' sslrootcert='/path with space/ca.pem' sslkey=/k\ ey dbname=''",
+        );
+
+        assert_eq!(
+            parsed.root_cert,
+            Some(PathBuf::from("/path with space/ca.pem"))
+        );
+        assert_eq!(parsed.key, Some(PathBuf::from("/k ey")));
+        assert_eq!(
+            parsed.connection,
+            r"host=db user=root password='a b\'c\\d' dbname='' sslmode=prefer"
+        );
+
+        let config: tokio_postgres::Config = parsed.connection.parse().unwrap();
+        assert_eq!(config.get_password(), Some(&br"a b'c\d"[..]));
+        assert_eq!(config.get_user(), Some("root"));
+
+        assert!(ConnectionString::parse("host=db password='open").is_err());
+        assert!(ConnectionString::parse("host").is_err());
+        assert!(ConnectionString::parse("=db").is_err());
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("a%20b%2Fc").unwrap(), "a b/c");
+        assert_eq!(percent_decode("%C3%A9").unwrap(), "é");
+        assert_eq!(percent_decode("plain").unwrap(), "plain");
+        assert!(percent_decode("%2").is_err());
+        assert!(percent_decode("%zz").is_err());
+        assert!(percent_decode("%FF").is_err());
+    }
+}
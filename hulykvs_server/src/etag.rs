@@ -0,0 +1,216 @@
+//
+// Copyright © 2025 Hardcore Engineering Inc.
+//
+// Licensed under the Eclipse Public License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License. You may
+// obtain a copy of the License at https://www.eclipse.org/legal/epl-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Entity tags (RFC 9110, section 8.8.3) and the `If-Match` / `If-None-Match`
+//! preconditions built from them.
+//!
+//! The entity tag of a stored value is its md5, sent as a quoted strong tag.
+//! Bare hex tags (as emitted by earlier versions) are still accepted.
+
+use actix_web::{
+    Error, HttpRequest, error,
+    http::header::{HeaderName, IF_MATCH, IF_NONE_MATCH},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    /// `*`
+    Any,
+    /// List of md5 tags, tags which can not be an md5 are dropped since they never match.
+    Tags(Vec<[u8; 16]>),
+}
+
+impl Condition {
+    /// Parses every occurrence of `name`, `None` if the header is absent. Weak tags
+    /// are dropped with `strong` comparison.
+    pub fn from_request(
+        req: &HttpRequest,
+        name: HeaderName,
+        strong: bool,
+    ) -> Result<Option<Self>, Error> {
+        let mut values = req.headers().get_all(&name).peekable();
+
+        if values.peek().is_none() {
+            return Ok(None);
+        }
+
+        let malformed = || error::ErrorBadRequest(format!("Malformed {name} header"));
+
+        let values = values
+            .map(|value| value.to_str())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| malformed())?;
+
+        Self::parse(values, strong).map(Some).ok_or_else(malformed)
+    }
+
+    /// Parses comma separated lists of tags or `*`, `None` if malformed.
+    pub fn parse<'a>(values: impl IntoIterator<Item = &'a str>, strong: bool) -> Option<Self> {
+        let mut any = false;
+        let mut tags = Vec::new();
+
+        for value in values {
+            for item in split_list(value)? {
+                if item == "*" {
+                    any = true;
+                } else {
+                    let (weak, md5) = parse_tag(item)?;
+
+                    if !(weak && strong) {
+                        tags.extend(md5);
+                    }
+                }
+            }
+        }
+
+        match (any, tags.is_empty()) {
+            (true, true) => Some(Condition::Any),
+            (true, false) => None,
+            (false, _) => Some(Condition::Tags(tags)),
+        }
+    }
+
+    /// `If-Match` of the request, compared strongly.
+    pub fn if_match(req: &HttpRequest) -> Result<Option<Self>, Error> {
+        Self::from_request(req, IF_MATCH, true)
+    }
+
+    /// `If-None-Match` of the request, compared weakly.
+    pub fn if_none_match(req: &HttpRequest) -> Result<Option<Self>, Error> {
+        Self::from_request(req, IF_NONE_MATCH, false)
+    }
+
+    pub fn is_any(&self) -> bool {
+        matches!(self, Condition::Any)
+    }
+
+    /// Tags as query parameter (`bytea[]`), empty for `*`.
+    pub fn tags(&self) -> Vec<&[u8]> {
+        match self {
+            Condition::Any => Vec::new(),
+            Condition::Tags(tags) => tags.iter().map(|tag| &tag[..]).collect(),
+        }
+    }
+
+    /// Weak tags are already dropped for strong comparison.
+    pub fn matches(&self, md5: &[u8]) -> bool {
+        match self {
+            Condition::Any => true,
+            Condition::Tags(tags) => tags.iter().any(|tag| tag == md5),
+        }
+    }
+}
+
+/// Non-empty trimmed items of a comma separated list, commas within quoted tags do
+/// not separate items. `None` if a quote is not closed.
+fn split_list(value: &str) -> Option<Vec<&str>> {
+    let mut items = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+
+    for (index, c) in value.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                items.push(&value[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if quoted {
+        return None;
+    }
+
+    items.push(&value[start..]);
+
+    Some(
+        items
+            .into_iter()
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect(),
+    )
+}
+
+/// Weakness and md5 of a tag, the md5 is `None` for a well-formed tag which is not an md5.
+fn parse_tag(item: &str) -> Option<(bool, Option<[u8; 16]>)> {
+    let opaque = item.strip_prefix("W/").unwrap_or(item);
+    let weak = opaque.len() != item.len();
+
+    match opaque.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(opaque) => {
+            // etagc = %x21 / %x23-7E / obs-text
+            if opaque.bytes().any(|b| b <= 0x20 || b == b'"' || b == 0x7f) {
+                return None;
+            }
+
+            Some((
+                weak,
+                hex::decode(opaque).ok().and_then(|v| v.try_into().ok()),
+            ))
+        }
+
+        None if !weak => {
+            let md5 = hex::decode(item).ok()?.try_into().ok()?;
+            Some((false, Some(md5)))
+        }
+
+        // W/ requires a quoted tag
+        None => None,
+    }
+}
+
+/// ETag header value of a stored md5, a quoted strong tag.
+pub fn format(md5: &[u8]) -> String {
+    format!("\"{}\"", hex::encode(md5))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MD5: &str = "552e21cd4cd9918678e3c1a0df491bc3";
+
+    fn md5() -> [u8; 16] {
+        hex::decode(MD5).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn lists() {
+        let parse = |value: &str| Condition::parse([value], true);
+
+        assert_eq!(parse("*"), Some(Condition::Any));
+        assert_eq!(parse(MD5), Some(Condition::Tags(vec![md5()])));
+        assert_eq!(
+            parse(&format!(r#""a,b", W/"{MD5}", "{MD5}""#)),
+            Some(Condition::Tags(vec![md5()]))
+        );
+        assert_eq!(
+            parse(&format!(r#""{MD5},x""#)),
+            Some(Condition::Tags(vec![]))
+        );
+        assert_eq!(parse(r#""a,b"#), None);
+        assert_eq!(parse(&format!(r#"*, "{MD5}""#)), None);
+        assert_eq!(parse(r#"W/a"#), None);
+    }
+
+    #[test]
+    fn weak_comparison() {
+        let condition = Condition::parse([format!(r#"W/"{MD5}""#).as_str()], false).unwrap();
+        assert!(condition.matches(&md5()));
+    }
+}
@@ -16,21 +16,28 @@
 use crate::config::CONFIG;
 
 use actix_web::{
-    HttpResponse, error,
+    HttpRequest, HttpResponse, error,
     web::{self, Data, Json, Query},
 };
 use serde::{Deserialize, Serialize};
-use tracing::{error, trace};
+use tokio_postgres::types::ToSql;
+use tracing::{Instrument, error, trace};
 
 use super::Pool;
+use crate::policy::{Permission, authorize};
+use crate::prefix;
+use crate::telemetry;
 
 type BucketPath = web::Path<String>;
 type ObjectPath = web::Path<(String, String)>;
 
 pub async fn get(
+    req: HttpRequest,
     path: ObjectPath,
     pool: Data<Pool>,
 ) -> Result<HttpResponse, actix_web::error::Error> {
+    authorize(&req, Permission::Read)?; // Check namespace
+
     let (namespace, key) = path.into_inner();
     trace!(namespace, key, "get request");
 
@@ -38,7 +45,7 @@ pub async fn get(
     let keystr = key.as_str();
 
     async move || -> anyhow::Result<HttpResponse> {
-        let connection = pool.get().await?;
+        let connection = pool.get().instrument(telemetry::db("connect")).await?;
 
         let statement = r#"
            select value from kvs where workspace=$1 and namespace=$2 and key=$3
@@ -49,6 +56,7 @@ pub async fn get(
                 statement,
                 &[&CONFIG.default_workspace_uuid, &nsstr, &keystr],
             )
+            .instrument(telemetry::db("select"))
             .await?;
 
         let response = match result.as_slice() {
@@ -67,10 +75,13 @@ pub async fn get(
 }
 
 pub async fn post(
+    req: HttpRequest,
     path: ObjectPath,
     pool: Data<Pool>,
     body: web::Bytes,
 ) -> Result<HttpResponse, actix_web::error::Error> {
+    authorize(&req, Permission::Write)?; // Check namespace
+
     let (namespace, key) = path.into_inner();
     trace!(namespace, key, "post request");
 
@@ -78,7 +89,7 @@ pub async fn post(
     let keystr = key.as_str();
 
     async move || -> anyhow::Result<HttpResponse> {
-        let connection = pool.get().await?;
+        let connection = pool.get().instrument(telemetry::db("connect")).await?;
 
         let md5 = md5::compute(&body);
 
@@ -88,7 +99,8 @@ pub async fn post(
            on conflict(workspace, namespace, key)
            do update set 
             md5=excluded.md5, 
-            value=excluded.value
+            value=excluded.value,
+            updated=now()
         "#;
 
         connection
@@ -102,6 +114,7 @@ pub async fn post(
                     &&body[..],
                 ],
             )
+            .instrument(telemetry::db("upsert"))
             .await?;
 
         Ok(HttpResponse::NoContent().finish())
@@ -114,9 +127,12 @@ pub async fn post(
 }
 
 pub async fn delete(
+    req: HttpRequest,
     path: ObjectPath,
     pool: Data<Pool>,
 ) -> Result<HttpResponse, actix_web::error::Error> {
+    authorize(&req, Permission::Write)?; // Check namespace
+
     let (namespace, key) = path.into_inner();
     trace!(namespace, key, "delete request");
 
@@ -124,7 +140,7 @@ pub async fn delete(
     let keystr = key.as_str();
 
     async move || -> anyhow::Result<HttpResponse> {
-        let connection = pool.get().await?;
+        let connection = pool.get().instrument(telemetry::db("connect")).await?;
 
         let statement = r#"
            delete from kvs where workspace=$1 and namespace=$2 and key=$3
@@ -135,6 +151,7 @@ pub async fn delete(
                 statement,
                 &[&CONFIG.default_workspace_uuid, &nsstr, &keystr],
             )
+            .instrument(telemetry::db("delete"))
             .await?
         {
             1 => HttpResponse::NoContent(),
@@ -164,29 +181,45 @@ pub struct ListResponse {
 }
 
 pub async fn list(
+    req: HttpRequest,
     path: BucketPath,
     pool: Data<Pool>,
     query: Query<ListInfo>,
 ) -> Result<Json<ListResponse>, actix_web::error::Error> {
+    authorize(&req, Permission::List)?; // Check namespace
+
     let namespace = path.into_inner();
     trace!(namespace, prefix = ?query.prefix, "enumerate request");
 
     let nsstr = namespace.as_str();
 
     async move || -> anyhow::Result<Json<ListResponse>> {
-        let connection = pool.get().await?;
-
-        let response = if let Some(prefix) = &query.prefix {
-            let pattern = format!("{}%", prefix);
-            let statement = r#"
-                select key from kvs where workspace=$1 and namespace=$2 and key like $3
-            "#;
+        let connection = pool.get().instrument(telemetry::db("connect")).await?;
+
+        let response = if let Some(prefix) = query.prefix.as_ref().filter(|p| !p.is_empty()) {
+            // literal prefix as a key range
+            let prefix_end = prefix::successor(prefix);
+
+            let mut params: Vec<&(dyn ToSql + Sync)> =
+                vec![&CONFIG.default_workspace_uuid, &nsstr, prefix];
+
+            let statement = match &prefix_end {
+                Some(prefix_end) => {
+                    params.push(prefix_end);
+                    r#"
+                        select key from kvs where workspace=$1 and namespace=$2 and key >= $3 and key < $4
+                    "#
+                }
+                None => {
+                    r#"
+                        select key from kvs where workspace=$1 and namespace=$2 and key >= $3
+                    "#
+                }
+            };
 
             connection
-                .query(
-                    statement,
-                    &[&CONFIG.default_workspace_uuid, &nsstr, &pattern],
-                )
+                .query(statement, &params)
+                .instrument(telemetry::db("select"))
                 .await?
         } else {
             let statement = r#"
@@ -195,6 +228,7 @@ pub async fn list(
 
             connection
                 .query(statement, &[&CONFIG.default_workspace_uuid, &nsstr])
+                .instrument(telemetry::db("select"))
                 .await?
         };
 
@@ -13,19 +13,38 @@
 // limitations under the License.
 //
 
+use std::{
+    convert::Infallible,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, SystemTime},
+};
+
 use uuid::Uuid;
 
 use actix_web::{
-    Error, HttpMessage, HttpRequest, HttpResponse, error,
+    Error, HttpMessage, HttpRequest, HttpResponse,
+    body::{BodySize, MessageBody},
+    error,
+    http::header::{ACCEPT, ETAG, HttpDate, LAST_MODIFIED},
     web::{self, Data, Json, Query},
 };
 
 use hulyrs::services::jwt::Claims;
 
+use base64::prelude::*;
+use chrono::{DateTime, Utc};
+use futures_util::{TryStreamExt, stream};
 use serde::{Deserialize, Serialize};
-use tracing::{error, trace};
+use tokio_postgres::{self as pg, types::ToSql};
+use tracing::{Instrument, debug, error, trace};
 
 use super::Pool;
+use crate::config::CONFIG;
+use crate::etag::{self, Condition};
+use crate::policy::{Permission, authorize, authorize_namespace};
+use crate::prefix;
+use crate::telemetry;
 
 type BucketPath = web::Path<(String, String)>;
 type ObjectPath = web::Path<(String, String, String)>;
@@ -36,6 +55,7 @@ pub async fn get(
     pool: Data<Pool>,
 ) -> Result<HttpResponse, actix_web::error::Error> {
     workspace_owner(&req)?; // Check workspace
+    authorize(&req, Permission::Read)?; // Check namespace
 
     let (workspace, namespace, key) = path.into_inner();
     trace!(workspace, namespace, key, "get request");
@@ -45,26 +65,41 @@ pub async fn get(
     let nsstr = namespace.as_str();
     let keystr = key.as_str();
 
+    let if_none_match = Condition::if_none_match(&req)?;
+
     async move || -> anyhow::Result<HttpResponse> {
-        let connection = pool.get().await?;
+        let connection = pool.get().instrument(telemetry::db("connect")).await?;
 
+        // the value is not fetched if the client already has it
         let statement = r#"
-           select value, md5 from kvs where workspace=$1 and namespace=$2 and key=$3
+           select md5, case when $4 or md5 = any($5) then null else value end as value
+           from kvs where workspace=$1 and namespace=$2 and key=$3
         "#;
 
+        let any = if_none_match.as_ref().is_some_and(Condition::is_any);
+        let tags = if_none_match
+            .as_ref()
+            .map(Condition::tags)
+            .unwrap_or_default();
+
         let result = connection
-            .query(statement, &[&wsuuid, &nsstr, &keystr])
+            .query(statement, &[&wsuuid, &nsstr, &keystr, &any, &tags])
+            .instrument(telemetry::db("select"))
             .await?;
 
         let response = match result.as_slice() {
             [] => HttpResponse::NotFound().finish(),
             [row] => {
-                let value: Vec<u8> = row.get("value");
                 let md5: &[u8] = row.get("md5");
-                let md5_hex = hex::encode(md5);
-                HttpResponse::Ok()
-                    .insert_header(("ETag", md5_hex))
-                    .body(value)
+
+                match row.get::<_, Option<Vec<u8>>>("value") {
+                    Some(value) => HttpResponse::Ok()
+                        .insert_header((ETAG, etag::format(md5)))
+                        .body(value),
+                    None => HttpResponse::NotModified()
+                        .insert_header((ETAG, etag::format(md5)))
+                        .finish(),
+                }
             }
             _ => panic!("multiple rows found, unique constraint is probably violated"),
         };
@@ -85,133 +120,214 @@ pub async fn get(
     })
 }
 
-pub async fn put(
+/// Empty body announcing the size of the stored value, the encoder writes it
+/// as Content-Length of a HEAD response.
+struct HeadBody(u64);
+
+impl MessageBody for HeadBody {
+    type Error = Infallible;
+
+    fn size(&self) -> BodySize {
+        BodySize::Sized(self.0)
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Option<Result<web::Bytes, Self::Error>>> {
+        Poll::Ready(None)
+    }
+}
+
+pub async fn head(
     req: HttpRequest,
     path: ObjectPath,
     pool: Data<Pool>,
-    body: web::Bytes,
 ) -> Result<HttpResponse, actix_web::error::Error> {
     workspace_owner(&req)?; // Check workspace
+    authorize(&req, Permission::Read)?; // Check namespace
 
     let (workspace, namespace, key) = path.into_inner();
-    trace!(workspace, namespace, key, "update request");
+    trace!(workspace, namespace, key, "head request");
 
     let wsuuid = Uuid::parse_str(workspace.as_str())
         .map_err(|e| error::ErrorBadRequest(format!("Invalid UUID in workspace: {}", e)))?;
     let nsstr = namespace.as_str();
     let keystr = key.as_str();
 
-    // header If-Match
-    let if_match_header = req.headers().get("If-Match").and_then(|h| h.to_str().ok());
+    let if_none_match = Condition::if_none_match(&req)?;
 
-    // header If-None-Match (only *)
-    let if_none_match = match req.headers().get("If-None-Match") {
-        Some(value) => {
-            let value_str = value
-                .to_str()
-                .map_err(|_| error::ErrorBadRequest("Invalid If-None-Match header encoding"))?;
+    async move || -> anyhow::Result<HttpResponse> {
+        let connection = pool.get().instrument(telemetry::db("connect")).await?;
+
+        // size is a stored column, the value itself is never read
+        let statement = r#"
+           select md5, size, updated from kvs where workspace=$1 and namespace=$2 and key=$3
+        "#;
 
-            if value_str.trim() == "*" {
-                true
-            } else {
-                return Err(error::ErrorBadRequest("If-None-Match must be '*'"));
+        let result = connection
+            .query(statement, &[&wsuuid, &nsstr, &keystr])
+            .instrument(telemetry::db("select"))
+            .await?;
+
+        let response = match result.as_slice() {
+            [] => HttpResponse::NotFound().finish(),
+            [row] => {
+                let md5: &[u8] = row.get("md5");
+                let size: i64 = row.get("size");
+                let updated: DateTime<Utc> = row.get("updated");
+                let last_modified = HttpDate::from(SystemTime::from(updated));
+
+                if if_none_match.is_some_and(|condition| condition.matches(md5)) {
+                    HttpResponse::NotModified()
+                        .insert_header((ETAG, etag::format(md5)))
+                        .insert_header((LAST_MODIFIED, last_modified))
+                        .finish()
+                } else {
+                    HttpResponse::Ok()
+                        .insert_header((ETAG, etag::format(md5)))
+                        .insert_header((LAST_MODIFIED, last_modified))
+                        .body(HeadBody(size as u64))
+                }
             }
-        }
-        None => false,
-    };
+            _ => panic!("multiple rows found, unique constraint is probably violated"),
+        };
+
+        Ok(response)
+    }()
+    .await
+    .map_err(|error| {
+        error!(
+            op = "head",
+            workspace,
+            namespace,
+            key,
+            ?error,
+            "internal error"
+        );
+        error::ErrorInternalServerError("")
+    })
+}
+
+pub async fn put(
+    req: HttpRequest,
+    path: ObjectPath,
+    pool: Data<Pool>,
+    body: web::Bytes,
+) -> Result<HttpResponse, actix_web::error::Error> {
+    workspace_owner(&req)?; // Check workspace
+    authorize(&req, Permission::Write)?; // Check namespace
+
+    let (workspace, namespace, key) = path.into_inner();
+    trace!(workspace, namespace, key, "update request");
+
+    let wsuuid = Uuid::parse_str(workspace.as_str())
+        .map_err(|e| error::ErrorBadRequest(format!("Invalid UUID in workspace: {}", e)))?;
+    let nsstr = namespace.as_str();
+    let keystr = key.as_str();
+
+    let if_match = Condition::if_match(&req)?;
+    let if_none_match = Condition::if_none_match(&req)?;
 
     async move || -> anyhow::Result<HttpResponse> {
-        let connection = pool.get().await?;
+        let connection = pool.get().instrument(telemetry::db("connect")).await?;
         let new_md5 = md5::compute(&body);
 
-        // If-None-Match: *
-        if if_none_match {
-            let result = connection
-                .execute(
-                    r#"
+        let (affected, mut response) = match (&if_match, &if_none_match) {
+            // If-None-Match: * ==> INSERT
+            (None, Some(Condition::Any)) => {
+                let statement = r#"
                     INSERT INTO kvs (workspace, namespace, key, md5, value)
                     VALUES ($1, $2, $3, $4, $5)
                     ON CONFLICT (workspace, namespace, key) DO NOTHING
-                    "#,
-                    &[&wsuuid, &nsstr, &keystr, &&new_md5[..], &&body[..]],
-                )
-                .await?;
-
-            return Ok(if result == 0 {
-                HttpResponse::PreconditionFailed().finish()
-            } else {
-                HttpResponse::Created().finish()
-            });
-        }
+                "#;
 
-        // If-Match
-        if let Some(if_match) = if_match_header {
-            if if_match.trim() == "*" {
-                // If-Match: *
                 let result = connection
                     .execute(
-                        r#"
-                        UPDATE kvs
-                        SET md5 = $4, value = $5
-                        WHERE workspace = $1 AND namespace = $2 AND key = $3
-                        "#,
+                        statement,
                         &[&wsuuid, &nsstr, &keystr, &&new_md5[..], &&body[..]],
                     )
+                    .instrument(telemetry::db("insert"))
                     .await?;
 
-                return Ok(if result == 0 {
-                    HttpResponse::PreconditionFailed().finish()
-                } else {
-                    HttpResponse::NoContent().finish()
-                });
-            } else {
-                // If-Match: some
-                let old_md5 = hex::decode(if_match.trim())
-                    .map_err(|_| anyhow::anyhow!("Invalid hex in If-Match"))?;
+                (result, HttpResponse::Created())
+            }
+
+            // If-Match (a missing key fails) ==> UPDATE
+            (Some(if_match), _) => {
+                let (none_any, none_tags) = match &if_none_match {
+                    Some(condition) => (condition.is_any(), condition.tags()),
+                    None => (false, Vec::new()),
+                };
+
+                let statement = r#"
+                    UPDATE kvs
+                    SET md5 = $4, value = $5, updated = now()
+                    WHERE workspace = $1 AND namespace = $2 AND key = $3
+                      AND ($6 OR md5 = any($7))
+                      AND NOT ($8 OR md5 = any($9))
+                "#;
 
                 let result = connection
                     .execute(
-                        r#"
-                        UPDATE kvs
-                        SET md5 = $5, value = $6
-                        WHERE workspace = $1 AND namespace = $2 AND key = $3 AND md5 = $4
-                        "#,
+                        statement,
                         &[
                             &wsuuid,
                             &nsstr,
                             &keystr,
-                            &&old_md5[..],
                             &&new_md5[..],
                             &&body[..],
+                            &if_match.is_any(),
+                            &if_match.tags(),
+                            &none_any,
+                            &none_tags,
                         ],
                     )
+                    .instrument(telemetry::db("update"))
                     .await?;
 
-                return Ok(if result == 0 {
-                    HttpResponse::PreconditionFailed().finish()
-                } else {
-                    HttpResponse::NoContent().finish()
-                });
+                (result, HttpResponse::NoContent())
             }
-        }
 
-        // No If-Match, no If-None-Match ==> UPSERT
-        let result = connection
-            .execute(
-                r#"
-                INSERT INTO kvs (workspace, namespace, key, md5, value)
-                VALUES ($1, $2, $3, $4, $5)
-                ON CONFLICT (workspace, namespace, key)
-                DO UPDATE SET md5 = EXCLUDED.md5, value = EXCLUDED.value
-                "#,
-                &[&wsuuid, &nsstr, &keystr, &&new_md5[..], &&body[..]],
-            )
-            .await?;
+            // No If-Match, If-None-Match: <etags> or nothing ==> UPSERT
+            (None, if_none_match) => {
+                let none_tags = if_none_match
+                    .as_ref()
+                    .map(Condition::tags)
+                    .unwrap_or_default();
+
+                let statement = r#"
+                    INSERT INTO kvs (workspace, namespace, key, md5, value)
+                    VALUES ($1, $2, $3, $4, $5)
+                    ON CONFLICT (workspace, namespace, key)
+                    DO UPDATE SET md5 = EXCLUDED.md5, value = EXCLUDED.value, updated = now()
+                    WHERE NOT (kvs.md5 = any($6))
+                "#;
 
-        Ok(if result == 0 {
-            anyhow::bail!("Not found")
-        } else {
-            HttpResponse::NoContent().finish()
+                let result = connection
+                    .execute(
+                        statement,
+                        &[
+                            &wsuuid,
+                            &nsstr,
+                            &keystr,
+                            &&new_md5[..],
+                            &&body[..],
+                            &none_tags,
+                        ],
+                    )
+                    .instrument(telemetry::db("upsert"))
+                    .await?;
+
+                (result, HttpResponse::NoContent())
+            }
+        };
+
+        Ok(match affected {
+            0 => HttpResponse::PreconditionFailed().finish(),
+            _ => response
+                .insert_header((ETAG, etag::format(&new_md5[..])))
+                .finish(),
         })
     }()
     .await
@@ -234,6 +350,7 @@ pub async fn delete(
     pool: Data<Pool>,
 ) -> Result<HttpResponse, actix_web::error::Error> {
     workspace_owner(&req)?; // Check workspace
+    authorize(&req, Permission::Write)?; // Check namespace
 
     let (workspace, namespace, key) = path.into_inner();
     trace!(workspace, namespace, key, "delete request");
@@ -243,18 +360,49 @@ pub async fn delete(
     let nsstr = namespace.as_str();
     let keystr = key.as_str();
 
+    let if_match = Condition::if_match(&req)?;
+
     async move || -> anyhow::Result<HttpResponse> {
-        let connection = pool.get().await?;
+        let connection = pool.get().instrument(telemetry::db("connect")).await?;
 
-        let statement = r#"
-            DELETE FROM kvs WHERE workspace=$1 AND namespace=$2 AND key=$3
-        "#;
+        let deleted = match &if_match {
+            None => {
+                let statement = r#"
+                    DELETE FROM kvs WHERE workspace=$1 AND namespace=$2 AND key=$3
+                "#;
 
-        let response = match connection
-            .execute(statement, &[&wsuuid, &nsstr, &keystr])
-            .await?
-        {
+                connection
+                    .execute(statement, &[&wsuuid, &nsstr, &keystr])
+                    .instrument(telemetry::db("delete"))
+                    .await?
+            }
+
+            Some(condition) => {
+                let statement = r#"
+                    DELETE FROM kvs
+                    WHERE workspace=$1 AND namespace=$2 AND key=$3 AND ($4 OR md5 = any($5))
+                "#;
+
+                connection
+                    .execute(
+                        statement,
+                        &[
+                            &wsuuid,
+                            &nsstr,
+                            &keystr,
+                            &condition.is_any(),
+                            &condition.tags(),
+                        ],
+                    )
+                    .instrument(telemetry::db("delete"))
+                    .await?
+            }
+        };
+
+        let response = match deleted {
             1 => HttpResponse::NoContent(),
+            // If-Match fails on a missing key as well
+            0 if if_match.is_some() => HttpResponse::PreconditionFailed(),
             0 => HttpResponse::NotFound(),
             _ => panic!("multiple rows deleted, unique constraint is probably violated"),
         };
@@ -275,9 +423,104 @@ pub async fn delete(
     })
 }
 
+/// Upper bound of `limit`.
+const LIST_LIMIT: u32 = 10_000;
+
+/// Keys per query of a streamed listing.
+const STREAM_CHUNK: usize = 1000;
+
+const NDJSON: &str = "application/x-ndjson";
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Values {
+    #[default]
+    None,
+    /// UTF-8 values as text, others in base64
+    Raw,
+    Base64,
+}
+
 #[derive(Deserialize)]
 pub struct ListInfo {
     prefix: Option<String>,
+    /// Page size, every key is returned if omitted
+    limit: Option<u32>,
+    /// `next_cursor` of the previous page
+    cursor: Option<String>,
+    /// Key to continue after, the plain form of `cursor`
+    start_after: Option<String>,
+    #[serde(default)]
+    order: Order,
+    /// Values of a page add up to at most `payload_size_limit`
+    #[serde(default)]
+    values: Values,
+    /// ETag, size and timestamps of every key
+    #[serde(default)]
+    metadata: bool,
+    /// Keys containing the delimiter after the prefix are grouped into `common_prefixes`
+    delimiter: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Base64,
+}
+
+#[derive(Serialize)]
+pub struct ListItem {
+    key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    updated: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encoding: Option<Encoding>,
+}
+
+impl ListItem {
+    fn from_row(row: &pg::Row, values: Values, metadata: bool) -> Self {
+        let value = match values {
+            Values::None => None,
+            Values::Raw => {
+                row.get::<_, Option<Vec<u8>>>("value")
+                    .map(|value| match String::from_utf8(value) {
+                        Ok(text) => (text, Encoding::Utf8),
+                        Err(error) => (BASE64_STANDARD.encode(error.as_bytes()), Encoding::Base64),
+                    })
+            }
+            Values::Base64 => row
+                .get::<_, Option<&[u8]>>("value")
+                .map(|value| (BASE64_STANDARD.encode(value), Encoding::Base64)),
+        };
+
+        ListItem {
+            key: row.get("key"),
+            etag: metadata.then(|| etag::format(row.get::<_, &[u8]>("md5"))),
+            size: metadata.then(|| row.get("size")),
+            created: metadata.then(|| row.get("created")),
+            updated: metadata.then(|| row.get("updated")),
+            encoding: value.as_ref().map(|(_, encoding)| *encoding),
+            value: value.map(|(value, _)| value),
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -286,6 +529,286 @@ pub struct ListResponse {
     namespace: String,
     count: usize,
     keys: Vec<String>,
+    /// Set if values or metadata are requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    items: Option<Vec<ListItem>>,
+    /// Set if a delimiter is given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    common_prefixes: Option<Vec<String>>,
+    /// Set if there are more keys than `limit`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+
+/// Key range and columns of a listing, read page by page.
+struct Listing {
+    workspace: Uuid,
+    namespace: String,
+    prefix: Option<String>,
+    prefix_end: Option<String>,
+    delimiter: Option<String>,
+    order: Order,
+    values: Values,
+    metadata: bool,
+}
+
+/// Entry of a page, in key order.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Entry {
+    Key(ListItem),
+    /// Keys sharing the prefix up to the delimiter
+    Prefix {
+        prefix: String,
+    },
+}
+
+impl Entry {
+    fn cursor(&self) -> &str {
+        match self {
+            Entry::Key(item) => &item.key,
+            Entry::Prefix { prefix } => prefix,
+        }
+    }
+}
+
+struct Page {
+    entries: Vec<Entry>,
+    /// Last key or common prefix of the page, if more follow
+    next: Option<String>,
+}
+
+/// Where the query of a page continues.
+enum Position {
+    Start,
+    After(String),
+    /// After every key of the common prefix
+    Past(String),
+}
+
+impl Listing {
+    fn detailed(&self) -> bool {
+        self.metadata || self.values != Values::None
+    }
+
+    /// The prefix and the rest of `key` up to and including the delimiter,
+    /// `None` if the key is not grouped.
+    fn group(&self, key: &str) -> Option<String> {
+        let delimiter = self.delimiter.as_deref()?;
+        let prefix = self.prefix.as_deref().unwrap_or_default();
+        let end = key.strip_prefix(prefix)?.find(delimiter)?;
+
+        Some(key[..prefix.len() + end + delimiter.len()].to_owned())
+    }
+
+    /// Up to `limit` keys and common prefixes after `after`, cut short once
+    /// values exceed `payload_size_limit`.
+    async fn page(
+        &self,
+        connection: &pg::Client,
+        after: Option<&str>,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Page> {
+        let mut position = match after {
+            None => Position::Start,
+            // a common prefix continues after all of its keys
+            Some(after) => match self.group(after) {
+                Some(group) => Position::Past(group),
+                None => Position::After(after.to_owned()),
+            },
+        };
+
+        let mut entries = Vec::new();
+        let mut budget = CONFIG.payload_size_limit.bytes();
+
+        // keys of a common prefix are skipped by the next query, which
+        // continues past them
+        let more = loop {
+            // one more entry tells whether there is a next page
+            let fetch = limit.map(|limit| (limit + 1 - entries.len()) as i64);
+
+            let rows = self
+                .query(connection, &position, fetch, budget, entries.is_empty())
+                .await?;
+
+            let mut cut = false;
+
+            for row in &rows {
+                if self.values != Values::None && !row.get::<_, bool>("fits") {
+                    cut = true;
+                    break;
+                }
+
+                let key: String = row.get("key");
+
+                match self.group(&key) {
+                    Some(group) if matches!(&position, Position::Past(past) if *past == group) => {}
+                    Some(group) => {
+                        entries.push(Entry::Prefix {
+                            prefix: group.clone(),
+                        });
+                        position = Position::Past(group);
+                    }
+                    None => {
+                        if self.values != Values::None {
+                            budget -= row.get::<_, i64>("size");
+                        }
+
+                        entries.push(Entry::Key(ListItem::from_row(
+                            row,
+                            self.values,
+                            self.metadata,
+                        )));
+                        position = Position::After(key);
+                    }
+                }
+
+                if limit.is_some_and(|limit| entries.len() > limit) {
+                    break;
+                }
+            }
+
+            if let Some(limit) = limit
+                && entries.len() > limit
+            {
+                entries.truncate(limit);
+                break true;
+            }
+
+            if cut {
+                break true;
+            }
+
+            // every row of the range is read
+            if fetch.is_none_or(|fetch| (rows.len() as i64) < fetch) {
+                break false;
+            }
+        };
+
+        let next = match more {
+            true => entries.last().map(|entry| entry.cursor().to_owned()),
+            false => None,
+        };
+
+        Ok(Page { entries, next })
+    }
+
+    /// Up to `fetch` rows from `position` on, with values within `budget`
+    /// (the first row anyway, if `first`).
+    async fn query(
+        &self,
+        connection: &pg::Client,
+        position: &Position,
+        fetch: Option<i64>,
+        budget: i64,
+        first: bool,
+    ) -> anyhow::Result<Vec<pg::Row>> {
+        let (compare, direction) = match self.order {
+            Order::Asc => (">", "asc"),
+            Order::Desc => ("<", "desc"),
+        };
+
+        let bound = match position {
+            Position::Start => None,
+            Position::After(key) => Some((compare, key.clone())),
+            Position::Past(group) => match self.order {
+                Order::Asc => match prefix::successor(group) {
+                    Some(end) => Some((">=", end)),
+                    None => return Ok(Vec::new()),
+                },
+                Order::Desc => Some(("<", group.clone())),
+            },
+        };
+
+        let columns = match self.detailed() {
+            true => "key, md5, size, created, updated",
+            false => "key",
+        };
+
+        // keyset pagination over the primary key (workspace, namespace, key)
+        let mut statement =
+            format!("select {columns} from kvs where workspace=$1 and namespace=$2");
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&self.workspace, &self.namespace];
+
+        // literal prefix as a key range
+        let mut prefix_param = None;
+
+        if let Some(prefix) = &self.prefix {
+            params.push(prefix);
+            prefix_param = Some(params.len());
+            statement += &format!(" and key >= ${}", params.len());
+        }
+
+        if let Some(prefix_end) = &self.prefix_end {
+            params.push(prefix_end);
+            statement += &format!(" and key < ${}", params.len());
+        }
+
+        if let Some((compare, bound)) = &bound {
+            params.push(bound);
+            statement += &format!(" and key {compare} ${}", params.len());
+        }
+
+        statement += &format!(" order by key {direction}");
+
+        if let Some(fetch) = &fetch {
+            params.push(fetch);
+            statement += &format!(" limit ${}", params.len());
+        }
+
+        // values are read only for the keys within the budget, keys of common
+        // prefixes take no part in it
+        if self.values != Values::None {
+            let grouped = match &self.delimiter {
+                Some(delimiter) => {
+                    params.push(delimiter);
+
+                    let rest = match prefix_param {
+                        Some(prefix) => format!("substr(key, char_length(${prefix}) + 1)"),
+                        None => "key".to_owned(),
+                    };
+
+                    format!("strpos({rest}, ${}) > 0", params.len())
+                }
+                None => "false".to_owned(),
+            };
+
+            params.push(&budget);
+            let budget_param = params.len();
+            params.push(&first);
+            let first_param = params.len();
+
+            statement = format!(
+                r#"
+                select *, case when fits and not grouped then (
+                    select value from kvs v
+                    where v.workspace = $1 and v.namespace = $2 and v.key = page.key
+                ) end as value
+                from (
+                    select *,
+                        sum(case when grouped then 0 else size end) over w <= ${budget_param}::int8
+                        or (row_number() over w = 1 and ${first_param}) as fits
+                    from (select *, {grouped} as grouped from ({statement}) as keys) as keys
+                    window w as (order by key {direction})
+                ) as page
+                order by key {direction}
+                "#
+            );
+        }
+
+        let rows = connection
+            .query(&statement, &params)
+            .instrument(telemetry::db("select"))
+            .await?;
+
+        Ok(rows)
+    }
+}
+
+/// Last line of a streamed listing cut by `limit`.
+#[derive(Serialize)]
+struct StreamEnd {
+    next_cursor: String,
 }
 
 pub async fn list(
@@ -293,52 +816,593 @@ pub async fn list(
     path: BucketPath,
     pool: Data<Pool>,
     query: Query<ListInfo>,
-) -> Result<Json<ListResponse>, actix_web::error::Error> {
+) -> Result<HttpResponse, actix_web::error::Error> {
     workspace_owner(&req)?; // Check workspace
+    authorize(&req, Permission::List)?; // Check namespace
 
     let (workspace, namespace) = path.into_inner();
-    trace!(workspace, namespace, prefix = ?query.prefix, "list request");
+    trace!(workspace, namespace, prefix = ?query.prefix, limit = ?query.limit, "list request");
 
-    let wsstr = workspace.as_str();
-    let wsuuid = Uuid::parse_str(wsstr)
+    let wsuuid = Uuid::parse_str(workspace.as_str())
         .map_err(|e| error::ErrorBadRequest(format!("Invalid UUID in workspace: {}", e)))?;
 
+    // the cursor is the last key of the page, hex encoded
+    let after = match (&query.cursor, &query.start_after) {
+        (Some(_), Some(_)) => {
+            return Err(error::ErrorBadRequest(
+                "cursor and start_after are mutually exclusive",
+            ));
+        }
+        (Some(cursor), None) => Some(
+            hex::decode(cursor)
+                .ok()
+                .and_then(|key| String::from_utf8(key).ok())
+                .ok_or_else(|| error::ErrorBadRequest("Invalid cursor"))?,
+        ),
+        (None, start_after) => start_after.clone(),
+    };
+
+    let limit = match query.limit {
+        Some(0) => return Err(error::ErrorBadRequest("limit must be positive")),
+        limit => limit.map(|limit| limit.min(LIST_LIMIT) as usize),
+    };
+
+    let query = query.into_inner();
+    let prefix = query.prefix.filter(|prefix| !prefix.is_empty());
+
+    let listing = Listing {
+        workspace: wsuuid,
+        namespace: namespace.clone(),
+        prefix_end: prefix.as_deref().and_then(prefix::successor),
+        prefix,
+        delimiter: query.delimiter.filter(|delimiter| !delimiter.is_empty()),
+        order: query.order,
+        values: query.values,
+        metadata: query.metadata,
+    };
+
+    let stream = req
+        .headers()
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(NDJSON));
+
+    async move || -> anyhow::Result<HttpResponse> {
+        if stream {
+            let listing_ns = listing.namespace.clone();
+            let connection = pool
+                .get_owned()
+                .instrument(telemetry::db("connect"))
+                .await?;
+
+            let lines = stream::try_unfold(
+                (listing, connection, after, limit, false),
+                async |(listing, connection, after, remaining, done)| {
+                    if done {
+                        return anyhow::Ok(None);
+                    }
+
+                    let chunk =
+                        remaining.map_or(STREAM_CHUNK, |remaining| remaining.min(STREAM_CHUNK));
+                    let page = listing
+                        .page(&connection, after.as_deref(), Some(chunk))
+                        .await?;
+                    let remaining = remaining.map(|remaining| remaining - page.entries.len());
+
+                    let mut lines = Vec::new();
+
+                    for entry in &page.entries {
+                        serde_json::to_writer(&mut lines, entry)?;
+                        lines.push(b'\n');
+                    }
+
+                    // the limit is reached, the client continues with the cursor
+                    if let (Some(next), Some(0)) = (&page.next, remaining) {
+                        let end = StreamEnd {
+                            next_cursor: hex::encode(next),
+                        };
+                        serde_json::to_writer(&mut lines, &end)?;
+                        lines.push(b'\n');
+                    }
+
+                    let done = page.next.is_none() || remaining == Some(0);
+
+                    Ok(Some((
+                        web::Bytes::from(lines),
+                        (listing, connection, page.next, remaining, done),
+                    )))
+                },
+            )
+            .inspect_err(move |error| {
+                error!(op = "list", %wsuuid, namespace = listing_ns, ?error, "stream error");
+            });
+
+            return Ok(HttpResponse::Ok().content_type(NDJSON).streaming(lines));
+        }
+
+        let connection = pool.get().instrument(telemetry::db("connect")).await?;
+
+        let page = listing.page(&connection, after.as_deref(), limit).await?;
+
+        let mut keys = Vec::new();
+        let mut items = Vec::new();
+        let mut common_prefixes = Vec::new();
+
+        for entry in page.entries {
+            match entry {
+                Entry::Key(item) => {
+                    keys.push(item.key.clone());
+                    items.push(item);
+                }
+                Entry::Prefix { prefix } => common_prefixes.push(prefix),
+            }
+        }
+
+        Ok(HttpResponse::Ok().json(ListResponse {
+            count: keys.len(),
+            keys,
+            items: listing.detailed().then_some(items),
+            common_prefixes: listing.delimiter.is_some().then_some(common_prefixes),
+            next_cursor: page.next.map(hex::encode),
+            namespace: listing.namespace,
+            workspace: listing.workspace.to_string(),
+        }))
+    }()
+    .await
+    .map_err(|error| {
+        error!(op = "list", workspace, namespace, ?error, "internal error");
+        error::ErrorInternalServerError("")
+    })
+}
+
+/// Upper bound of keys of a batch get.
+const BATCH_LIMIT: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct BatchGet {
+    keys: Vec<String>,
+    /// Values in the response, `raw` by default
+    values: Option<Values>,
+}
+
+#[derive(Serialize)]
+pub struct BatchGetResponse {
+    workspace: String,
+    namespace: String,
+    /// Found keys in key order, with ETag, size and timestamps
+    items: Vec<ListItem>,
+    missing: Vec<String>,
+    /// Found keys beyond `payload_size_limit`, to be requested again
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    remaining: Vec<String>,
+}
+
+pub async fn batch_get(
+    req: HttpRequest,
+    path: BucketPath,
+    pool: Data<Pool>,
+    body: Json<BatchGet>,
+) -> Result<Json<BatchGetResponse>, actix_web::error::Error> {
+    workspace_owner(&req)?; // Check workspace
+    authorize(&req, Permission::Read)?; // Check namespace
+
+    let (workspace, namespace) = path.into_inner();
+    let BatchGet { mut keys, values } = body.into_inner();
+    trace!(workspace, namespace, keys = keys.len(), "batch get request");
+
+    let wsuuid = Uuid::parse_str(workspace.as_str())
+        .map_err(|e| error::ErrorBadRequest(format!("Invalid UUID in workspace: {}", e)))?;
     let nsstr = namespace.as_str();
 
-    async move || -> anyhow::Result<Json<ListResponse>> {
-        let connection = pool.get().await?;
+    if keys.len() > BATCH_LIMIT {
+        return Err(error::ErrorBadRequest(format!(
+            "at most {BATCH_LIMIT} keys per request"
+        )));
+    }
+
+    keys.sort();
+    keys.dedup();
+
+    let values = values.unwrap_or(Values::Raw);
 
-        let response = if let Some(prefix) = &query.prefix {
-            let pattern = format!("{}%", prefix);
-            let statement = r#"
-                select key from kvs where workspace=$1 and namespace=$2 and key like $3
-            "#;
+    async move || -> anyhow::Result<Json<BatchGetResponse>> {
+        let connection = pool.get().instrument(telemetry::db("connect")).await?;
 
-            connection
-                .query(statement, &[&wsuuid, &nsstr, &pattern])
-                .await?
-        } else {
-            let statement = r#"
-                select key from kvs where workspace=$1 and namespace=$2
-            "#;
+        let budget = CONFIG.payload_size_limit.bytes();
 
-            connection.query(statement, &[&wsuuid, &nsstr]).await?
+        // values are read only for the keys within the budget, the first one
+        // is returned anyway
+        let statement = match values {
+            Values::None => {
+                r#"
+                select key, md5, size, created, updated, true as fits
+                from kvs where workspace=$1 and namespace=$2 and key = any($3)
+                order by key
+            "#
+            }
+            _ => {
+                r#"
+                select *, case when fits then (
+                    select value from kvs v
+                    where v.workspace = $1 and v.namespace = $2 and v.key = found.key
+                ) end as value
+                from (
+                    select key, md5, size, created, updated,
+                        sum(size) over w <= $4::int8 or row_number() over w = 1 as fits
+                    from kvs where workspace=$1 and namespace=$2 and key = any($3)
+                    window w as (order by key)
+                ) as found
+                order by key
+            "#
+            }
         };
 
-        let count = response.len();
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&wsuuid, &nsstr, &keys];
 
-        let keys = response.into_iter().map(|row| row.get(0)).collect();
+        if values != Values::None {
+            params.push(&budget);
+        }
 
-        Ok(Json(ListResponse {
-            keys,
-            count,
+        let rows = connection
+            .query(statement, &params)
+            .instrument(telemetry::db("select"))
+            .await?;
+
+        let mut items = Vec::new();
+        let mut remaining = Vec::new();
+
+        for row in &rows {
+            match row.get::<_, bool>("fits") {
+                true => items.push(ListItem::from_row(row, values, true)),
+                false => remaining.push(row.get("key")),
+            }
+        }
+
+        let found = rows
+            .iter()
+            .map(|row| row.get::<_, &str>("key"))
+            .collect::<Vec<_>>();
+
+        // both are sorted
+        let missing = keys
+            .iter()
+            .filter(|key| found.binary_search(&key.as_str()).is_err())
+            .cloned()
+            .collect();
+
+        Ok(Json(BatchGetResponse {
+            workspace: wsuuid.to_string(),
             namespace: nsstr.to_owned(),
-            workspace: wsstr.to_owned(),
+            items,
+            missing,
+            remaining,
         }))
     }()
     .await
     .map_err(|error| {
-        error!(op = "list", workspace, namespace, ?error, "internal error");
+        error!(
+            op = "batch_get",
+            workspace,
+            namespace,
+            ?error,
+            "internal error"
+        );
+        error::ErrorInternalServerError("")
+    })
+}
+
+/// Upper bound of operations of a transaction.
+const TRANSACTION_LIMIT: usize = 1000;
+
+/// Attempts of a transaction aborted by a serialization conflict.
+const TRANSACTION_ATTEMPTS: u32 = 10;
+/// Upper bound of the first (random) delay between attempts, doubled on every conflict.
+const TRANSACTION_BACKOFF: Duration = Duration::from_millis(10);
+const TRANSACTION_BACKOFF_MAX: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    Put,
+    Delete,
+    Check,
+}
+
+#[derive(Deserialize)]
+pub struct Operation {
+    op: Op,
+    namespace: String,
+    key: String,
+    /// Value of a put
+    value: Option<String>,
+    #[serde(default)]
+    encoding: Encoding,
+    /// Same syntax as the header
+    if_match: Option<String>,
+    if_none_match: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Transaction {
+    operations: Vec<Operation>,
+}
+
+/// Operation with decoded value and conditions.
+struct Step {
+    op: Op,
+    namespace: String,
+    key: String,
+    value: Vec<u8>,
+    if_match: Option<Condition>,
+    if_none_match: Option<Condition>,
+}
+
+#[derive(Serialize)]
+pub struct StepResult {
+    /// ETag before the operation, `None` if the key did not exist
+    previous: Option<String>,
+    /// ETag after the operation, `None` if the key does not exist
+    etag: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct TransactionResponse {
+    results: Vec<StepResult>,
+}
+
+/// Body of the 412 response, nothing is applied.
+#[derive(Serialize)]
+pub struct PreconditionFailed {
+    index: usize,
+    op: Op,
+    namespace: String,
+    key: String,
+    /// `if_match` or `if_none_match`
+    condition: &'static str,
+    /// Current ETag, `None` if the key does not exist
+    etag: Option<String>,
+}
+
+impl Step {
+    /// Failing condition, given the current md5 of the key.
+    fn check(&self, md5: Option<&[u8]>) -> Option<&'static str> {
+        // If-Match fails on a missing key
+        if let Some(condition) = &self.if_match
+            && !md5.is_some_and(|md5| condition.matches(md5))
+        {
+            return Some("if_match");
+        }
+
+        if let Some(condition) = &self.if_none_match
+            && md5.is_some_and(|md5| condition.matches(md5))
+        {
+            return Some("if_none_match");
+        }
+
+        None
+    }
+}
+
+enum Outcome {
+    Committed(Vec<StepResult>),
+    Failed(PreconditionFailed),
+}
+
+/// Applies `steps` in a single serializable transaction, rolled back at the
+/// first failing precondition.
+async fn apply(
+    connection: &mut pg::Client,
+    workspace: &Uuid,
+    steps: &[Step],
+) -> anyhow::Result<Outcome> {
+    let transaction = connection
+        .build_transaction()
+        .isolation_level(pg::IsolationLevel::Serializable)
+        .start()
+        .instrument(telemetry::db("begin"))
+        .await?;
+
+    let mut results = Vec::with_capacity(steps.len());
+
+    for (index, step) in steps.iter().enumerate() {
+        // written keys are locked up front, checked keys are guarded by serializability
+        let statement = match step.op {
+            Op::Check => "select md5 from kvs where workspace=$1 and namespace=$2 and key=$3",
+            Op::Put | Op::Delete => {
+                "select md5 from kvs where workspace=$1 and namespace=$2 and key=$3 for update"
+            }
+        };
+
+        let row = transaction
+            .query_opt(statement, &[workspace, &step.namespace, &step.key])
+            .instrument(telemetry::db("select"))
+            .await?;
+
+        let md5 = row.map(|row| row.get::<_, Vec<u8>>("md5"));
+        let previous = md5.as_deref().map(etag::format);
+
+        if let Some(condition) = step.check(md5.as_deref()) {
+            transaction
+                .rollback()
+                .instrument(telemetry::db("rollback"))
+                .await?;
+
+            return Ok(Outcome::Failed(PreconditionFailed {
+                index,
+                op: step.op,
+                namespace: step.namespace.clone(),
+                key: step.key.clone(),
+                condition,
+                etag: previous,
+            }));
+        }
+
+        let etag = match step.op {
+            Op::Check => previous.clone(),
+
+            Op::Put => {
+                let statement = r#"
+                    INSERT INTO kvs (workspace, namespace, key, md5, value)
+                    VALUES ($1, $2, $3, $4, $5)
+                    ON CONFLICT (workspace, namespace, key)
+                    DO UPDATE SET md5 = EXCLUDED.md5, value = EXCLUDED.value, updated = now()
+                "#;
+
+                let new_md5 = md5::compute(&step.value);
+
+                transaction
+                    .execute(
+                        statement,
+                        &[
+                            workspace,
+                            &step.namespace,
+                            &step.key,
+                            &&new_md5[..],
+                            &step.value,
+                        ],
+                    )
+                    .instrument(telemetry::db("upsert"))
+                    .await?;
+
+                Some(etag::format(&new_md5[..]))
+            }
+
+            Op::Delete => {
+                let statement = "delete from kvs where workspace=$1 and namespace=$2 and key=$3";
+
+                transaction
+                    .execute(statement, &[workspace, &step.namespace, &step.key])
+                    .instrument(telemetry::db("delete"))
+                    .await?;
+
+                None
+            }
+        };
+
+        results.push(StepResult { previous, etag });
+    }
+
+    transaction
+        .commit()
+        .instrument(telemetry::db("commit"))
+        .await?;
+
+    Ok(Outcome::Committed(results))
+}
+
+/// Conflict of concurrent transactions, the transaction can be retried.
+fn is_serialization_failure(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<pg::Error>()
+        .and_then(pg::Error::code)
+        .is_some_and(|code| *code == pg::error::SqlState::T_R_SERIALIZATION_FAILURE)
+}
+
+pub async fn transaction(
+    req: HttpRequest,
+    path: web::Path<String>,
+    pool: Data<Pool>,
+    body: Json<Transaction>,
+) -> Result<HttpResponse, actix_web::error::Error> {
+    workspace_owner(&req)?; // Check workspace
+
+    let workspace = path.into_inner();
+    let Transaction { operations } = body.into_inner();
+    trace!(
+        workspace,
+        operations = operations.len(),
+        "transaction request"
+    );
+
+    let wsuuid = Uuid::parse_str(workspace.as_str())
+        .map_err(|e| error::ErrorBadRequest(format!("Invalid UUID in workspace: {}", e)))?;
+
+    if operations.len() > TRANSACTION_LIMIT {
+        return Err(error::ErrorBadRequest(format!(
+            "at most {TRANSACTION_LIMIT} operations per transaction"
+        )));
+    }
+
+    let steps = operations
+        .into_iter()
+        .enumerate()
+        .map(|(index, operation)| {
+            let permission = match operation.op {
+                Op::Check => Permission::Read,
+                Op::Put | Op::Delete => Permission::Write,
+            };
+
+            authorize_namespace(&req, &operation.namespace, permission)?; // Check namespace
+
+            let invalid =
+                |message: &str| error::ErrorBadRequest(format!("Operation {index}: {message}"));
+
+            let value = match (operation.op, operation.value) {
+                (Op::Put, Some(value)) => match operation.encoding {
+                    Encoding::Utf8 => value.into_bytes(),
+                    Encoding::Base64 => BASE64_STANDARD
+                        .decode(value)
+                        .map_err(|_| invalid("invalid base64 value"))?,
+                },
+                (Op::Put, None) => return Err(invalid("missing value")),
+                (_, Some(_)) => return Err(invalid("value is only allowed with put")),
+                (_, None) => Vec::new(),
+            };
+
+            let if_match = operation
+                .if_match
+                .map(|value| Condition::parse([value.as_str()], true))
+                .map(|condition| condition.ok_or_else(|| invalid("malformed if_match")))
+                .transpose()?;
+
+            let if_none_match = operation
+                .if_none_match
+                .map(|value| Condition::parse([value.as_str()], false))
+                .map(|condition| condition.ok_or_else(|| invalid("malformed if_none_match")))
+                .transpose()?;
+
+            Ok(Step {
+                op: operation.op,
+                namespace: operation.namespace,
+                key: operation.key,
+                value,
+                if_match,
+                if_none_match,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    async move || -> anyhow::Result<HttpResponse> {
+        let mut connection = pool.get().instrument(telemetry::db("connect")).await?;
+
+        let mut attempt = 1;
+
+        // conflicts are retried with the whole transaction
+        let outcome = loop {
+            match apply(&mut connection, &wsuuid, &steps).await {
+                Err(error)
+                    if is_serialization_failure(&error) && attempt < TRANSACTION_ATTEMPTS =>
+                {
+                    debug!(%wsuuid, attempt, "transaction conflict, retrying");
+
+                    // jitter keeps conflicting transactions from retrying in lockstep
+                    let backoff =
+                        (TRANSACTION_BACKOFF * 2u32.pow(attempt - 1)).min(TRANSACTION_BACKOFF_MAX);
+                    tokio::time::sleep(backoff.mul_f64(rand::random())).await;
+                    attempt += 1;
+                }
+                result => break result?,
+            }
+        };
+
+        Ok(match outcome {
+            Outcome::Committed(results) => HttpResponse::Ok().json(TransactionResponse { results }),
+            Outcome::Failed(failed) => HttpResponse::PreconditionFailed().json(failed),
+        })
+    }()
+    .await
+    .map_err(|error| {
+        error!(op = "transaction", workspace, ?error, "internal error");
         error::ErrorInternalServerError("")
     })
 }
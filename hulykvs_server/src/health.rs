@@ -0,0 +1,200 @@
+//
+// Copyright © 2025 Hardcore Engineering Inc.
+//
+// Licensed under the Eclipse Public License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License. You may
+// obtain a copy of the License at https://www.eclipse.org/legal/epl-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use std::{
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    Error, HttpResponse,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    error,
+    middleware::Next,
+    web::Data,
+};
+use serde::Serialize;
+use tracing::{debug, info, warn};
+
+use super::Pool;
+
+/// Upper bound of the database check, probes usually time out after a few seconds.
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum Migrations {
+    Pending,
+    /// Another instance holds the migration lock
+    Waiting,
+    Running,
+    Done,
+}
+
+/// Shared service state reported by the probes.
+pub struct Health {
+    migrations: AtomicU8,
+    /// Readiness reported by the last probe, to log only its changes
+    ready: AtomicBool,
+    pool_size: u32,
+}
+
+impl Health {
+    pub fn new(pool_size: u32) -> Self {
+        Self {
+            migrations: AtomicU8::new(Migrations::Pending as u8),
+            // so that a service starting unready logs it once
+            ready: AtomicBool::new(true),
+            pool_size,
+        }
+    }
+
+    pub fn migrations(&self) -> Migrations {
+        match self.migrations.load(Ordering::Acquire) {
+            0 => Migrations::Pending,
+            1 => Migrations::Waiting,
+            2 => Migrations::Running,
+            _ => Migrations::Done,
+        }
+    }
+
+    pub fn set_migrations(&self, state: Migrations) {
+        self.migrations.store(state as u8, Ordering::Release);
+    }
+}
+
+#[derive(Serialize)]
+struct DatabaseStatus {
+    ok: bool,
+    latency_ms: Option<u128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PoolStatus {
+    max_size: u32,
+    connections: u32,
+    idle: u32,
+    in_use: u32,
+    /// in_use / max_size
+    saturation: f64,
+    get_waited: u64,
+    get_timed_out: u64,
+}
+
+#[derive(Serialize)]
+struct Readiness {
+    ready: bool,
+    migrations: Migrations,
+    database: DatabaseStatus,
+    pool: PoolStatus,
+}
+
+/// Liveness: the process serves requests.
+pub async fn live() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "live": true }))
+}
+
+/// Readiness: migrations are applied and the database answers a query.
+pub async fn ready(pool: Data<Pool>, health: Data<Health>) -> HttpResponse {
+    let migrations = health.migrations();
+
+    let started = Instant::now();
+
+    let check = async {
+        let connection = pool.get().await.map_err(|e| e.to_string())?;
+        connection
+            .query_one("select 1", &[])
+            .await
+            .map_err(|e| e.to_string())
+    };
+
+    let database = match tokio::time::timeout(DATABASE_TIMEOUT, check).await {
+        Ok(Ok(_)) => DatabaseStatus {
+            ok: true,
+            latency_ms: Some(started.elapsed().as_millis()),
+            error: None,
+        },
+        Ok(Err(error)) => DatabaseStatus {
+            ok: false,
+            latency_ms: None,
+            error: Some(error),
+        },
+        Err(_) => DatabaseStatus {
+            ok: false,
+            latency_ms: None,
+            error: Some("timed out".to_owned()),
+        },
+    };
+
+    let state = pool.state();
+    let in_use = state.connections - state.idle_connections;
+
+    let pool = PoolStatus {
+        max_size: health.pool_size,
+        connections: state.connections,
+        idle: state.idle_connections,
+        in_use,
+        saturation: in_use as f64 / health.pool_size as f64,
+        get_waited: state.statistics.get_waited,
+        get_timed_out: state.statistics.get_timed_out,
+    };
+
+    let ready = database.ok && migrations == Migrations::Done;
+
+    // probes are frequent, only changes are worth more than debug
+    let changed = health.ready.swap(ready, Ordering::AcqRel) != ready;
+
+    match (ready, changed) {
+        (true, true) => info!("ready"),
+        (false, true) => warn!(?migrations, error = database.error, "not ready"),
+        (false, false) => debug!(?migrations, error = database.error, "not ready"),
+        (true, false) => {}
+    }
+
+    let readiness = Readiness {
+        ready,
+        migrations,
+        database,
+        pool,
+    };
+
+    if ready {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
+
+/// Rejects API requests until the schema is migrated.
+pub async fn migrated(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let done = request
+        .app_data::<Data<Health>>()
+        .is_some_and(|health| health.migrations() == Migrations::Done);
+
+    if !done {
+        return Err(error::ErrorServiceUnavailable(
+            "Database migration in progress",
+        ));
+    }
+
+    next.call(request).await
+}
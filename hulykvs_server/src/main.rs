@@ -21,22 +21,38 @@ use actix_web::{
     body::MessageBody,
     dev::{ServiceRequest, ServiceResponse},
     middleware::{self, Next},
-    web::{self, Data, PayloadConfig},
+    web::{self, Data, JsonConfig, PayloadConfig},
 };
 use bb8_postgres::PostgresConnectionManager;
+use clap::{Parser, Subcommand};
 use tokio_postgres as pg;
-use tracing::info;
+use tokio_postgres_rustls::MakeRustlsConnect;
+use tracing::{error, info, warn};
 
 mod config;
+mod db_tls;
+mod etag;
 mod handlers;
 mod handlers_v2;
+mod health;
+mod metrics;
+mod migration_lock;
+mod policy;
+mod prefix;
+mod schema;
+mod telemetry;
+mod tls;
 
 use config::CONFIG;
+use health::{Health, Migrations};
+use migration_lock::MigrationLock;
 
 use hulyrs::services::jwt::actix::ServiceRequestExt;
 use secrecy::SecretString;
 
-pub type Pool = bb8::Pool<PostgresConnectionManager<tokio_postgres::NoTls>>;
+pub type Pool = bb8::Pool<PostgresConnectionManager<MakeRustlsConnect>>;
+
+const POOL_SIZE: u32 = 15;
 
 mod migrations_crdb {
     refinery::embed_migrations!("etc/migrations");
@@ -46,13 +62,43 @@ mod migrations_pg {
     refinery::embed_migrations!("etc/migrations_pg");
 }
 
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Migrate the database and serve the API (default)
+    Serve {
+        /// Do not migrate, wait until the schema is migrated by `migrate`
+        #[arg(long)]
+        no_migrate: bool,
+    },
+
+    /// Migrate the database and exit
+    Migrate {
+        /// Print pending migrations without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Check checksums of applied migrations against the embedded ones
+    Verify,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum DbBackend {
     Cockroach,
     Postgres,
 }
 
-fn initialize_tracing(level: tracing::Level) {
+fn initialize_tracing(
+    level: tracing::Level,
+    provider: Option<&opentelemetry_sdk::trace::SdkTracerProvider>,
+) {
     use tracing_subscriber::{filter::targets::Targets, prelude::*};
 
     let filter = Targets::default()
@@ -63,6 +109,7 @@ fn initialize_tracing(level: tracing::Level) {
     tracing_subscriber::registry()
         .with(filter)
         .with(format)
+        .with(provider.map(telemetry::layer))
         .init();
 }
 
@@ -111,9 +158,162 @@ async fn detect_db_backend(connection: &pg::Client) -> anyhow::Result<DbBackend>
     }
 }
 
+async fn migrate(pool: &Pool, health: &Health) -> anyhow::Result<()> {
+    let mut connection = pool.dedicated_connection().await?;
+    let backend = detect_db_backend(&connection).await?;
+
+    // query params cannot be bound in ddl statements
+    connection
+        .execute(
+            &format!("create schema if not exists {}", CONFIG.db_scheme),
+            &[],
+        )
+        .await?;
+
+    info!(?backend, "detected database backend");
+
+    // other instances wait here until the schema is migrated
+    health.set_migrations(Migrations::Waiting);
+    let lock = MigrationLock::acquire(&connection, pool, backend).await?;
+    health.set_migrations(Migrations::Running);
+
+    let result = run_migrations(&mut connection, backend).await;
+
+    if let Err(error) = lock.release(&connection).await {
+        // the lock goes with the connection or goes stale, a failed migration matters more
+        match &result {
+            Ok(()) => return Err(error),
+            Err(_) => error!(?error, "cannot release migration lock"),
+        }
+    }
+
+    result
+}
+
+async fn run_migrations(connection: &mut pg::Client, backend: DbBackend) -> anyhow::Result<()> {
+    let report = match backend {
+        DbBackend::Cockroach => {
+            migrations_crdb::migrations::runner()
+                .set_abort_divergent(false)
+                .set_migration_table_name(schema::table(backend))
+                .run_async(connection)
+                .await?
+        }
+        DbBackend::Postgres => {
+            migrations_pg::migrations::runner()
+                .set_migration_table_name(schema::table(backend))
+                .run_async(connection)
+                .await?
+        }
+    };
+
+    for m in report.applied_migrations().iter() {
+        // Patch default from Config
+        if m.to_string() == "V4__workspace_uuid" {
+            let sql = format!(
+                "UPDATE kvs SET workspace = '{}' WHERE workspace = 'f7c9c6d2-81d7-5ff4-9f42-8ab129bb12f0';",
+                CONFIG.default_workspace_uuid
+            );
+            connection.execute(&sql, &[]).await?;
+            info!(
+                uuid = %CONFIG.default_workspace_uuid,
+                "set default workspace"
+            );
+        }
+
+        info!(migration = m.to_string(), "applied migration");
+    }
+
+    Ok(())
+}
+
+/// Waits for the schema to be migrated by another process (`hulykvs migrate`).
+async fn wait_migrated(pool: &Pool, health: &Health) -> anyhow::Result<()> {
+    let mut connection = pool.dedicated_connection().await?;
+    let backend = detect_db_backend(&connection).await?;
+
+    health.set_migrations(Migrations::Waiting);
+
+    let mut logged = false;
+
+    loop {
+        let pending = schema::pending(&mut connection, backend).await?;
+
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        if !logged {
+            let pending = pending.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+            warn!(?pending, "schema is not migrated, waiting");
+            logged = true;
+        }
+
+        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+    }
+}
+
+async fn dry_run(pool: &Pool) -> anyhow::Result<()> {
+    let mut connection = pool.dedicated_connection().await?;
+    let backend = detect_db_backend(&connection).await?;
+
+    let pending = schema::pending(&mut connection, backend).await?;
+
+    if pending.is_empty() {
+        println!("{}: no pending migrations", schema::table(backend));
+    }
+
+    for m in pending {
+        println!("{}: pending {m}", schema::table(backend));
+    }
+
+    Ok(())
+}
+
+async fn verify(pool: &Pool) -> anyhow::Result<()> {
+    let mut connection = pool.dedicated_connection().await?;
+
+    let mut valid = true;
+
+    for backend in [DbBackend::Cockroach, DbBackend::Postgres] {
+        let table = schema::table(backend);
+
+        let Some(mismatches) = schema::verify(&mut connection, backend).await? else {
+            println!("{table}: not found");
+            continue;
+        };
+
+        for mismatch in &mismatches {
+            match mismatch {
+                schema::Mismatch::Divergent(m) => {
+                    println!("{table}: {m} differs from the embedded migration")
+                }
+                schema::Mismatch::Missing(m) => println!("{table}: {m} is not embedded"),
+            }
+        }
+
+        let pending = schema::pending(&mut connection, backend).await?.len();
+
+        if mismatches.is_empty() {
+            println!("{table}: ok, {pending} pending");
+        }
+
+        valid &= mismatches.is_empty();
+    }
+
+    if !valid {
+        anyhow::bail!("applied migrations do not match the embedded ones");
+    }
+
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    initialize_tracing(tracing::Level::DEBUG);
+    let cli = Cli::parse();
+
+    let provider = telemetry::provider()?;
+    initialize_tracing(tracing::Level::DEBUG, provider.as_ref());
 
     tracing::info!("{}/{}", env!("CARGO_BIN_NAME"), env!("CARGO_PKG_VERSION"));
 
@@ -122,66 +322,45 @@ async fn main() -> anyhow::Result<()> {
         "database connection string"
     );
 
-    let manager = bb8_postgres::PostgresConnectionManager::new_from_stringlike(
-        &CONFIG.db_connection,
-        tokio_postgres::NoTls,
-    )?;
+    if let Some(policy) = &*policy::POLICY {
+        info!(rules = policy.rules.len(), "loaded namespace policy");
+    }
+
+    let (connection, tls) = db_tls::connector(&CONFIG.db_connection)?;
+    let manager = bb8_postgres::PostgresConnectionManager::new_from_stringlike(connection, tls)?;
 
     let pool = bb8::Pool::builder()
-        .max_size(15)
+        .max_size(POOL_SIZE)
         .connection_customizer(Box::new(ConnectionCustomizer))
         .build(manager)
         .await?;
-    {
-        let mut connection = pool.dedicated_connection().await?;
-        let backend = detect_db_backend(&connection).await?;
-
-        // query params cannot be bound in ddl statements
-        connection
-            .execute(
-                &format!("create schema if not exists {}", CONFIG.db_scheme),
-                &[],
-            )
-            .await?;
-
-        info!(?backend, "detected database backend");
 
-        let report = match backend {
-            DbBackend::Cockroach => {
-                migrations_crdb::migrations::runner()
-                    .set_abort_divergent(false)
-                    .set_migration_table_name("migrations")
-                    .run_async(&mut connection)
-                    .await?
-            }
-            DbBackend::Postgres => {
-                migrations_pg::migrations::runner()
-                    .set_migration_table_name("migrations_pg")
-                    .run_async(&mut connection)
-                    .await?
-            }
-        };
-
-        for m in report.applied_migrations().iter() {
-            // Patch default from Config
-            if m.to_string() == "V4__workspace_uuid" {
-                let sql = format!(
-                    "UPDATE kvs SET workspace = '{}' WHERE workspace = 'f7c9c6d2-81d7-5ff4-9f42-8ab129bb12f0';",
-                    CONFIG.default_workspace_uuid
-                );
-                connection.execute(&sql, &[]).await?;
-                info!(
-                    uuid = %CONFIG.default_workspace_uuid,
-                    "set default workspace"
-                );
-            }
-
-            info!(migration = m.to_string(), "applied migration");
+    let no_migrate = match cli.command {
+        None => false,
+        Some(Command::Serve { no_migrate }) => no_migrate,
+        Some(Command::Migrate { dry_run: true }) => return dry_run(&pool).await,
+        Some(Command::Migrate { dry_run: false }) => {
+            return migrate(&pool, &Health::new(POOL_SIZE)).await;
         }
-    }
+        Some(Command::Verify) => return verify(&pool).await,
+    };
 
     let socket = std::net::SocketAddr::new(CONFIG.bind_host.as_str().parse()?, CONFIG.bind_port);
+
+    let tls = match (&CONFIG.tls_cert, &CONFIG.tls_key) {
+        (Some(cert), Some(key)) => Some(tls::server_config(cert, key)?),
+        (None, None) => None,
+        _ => anyhow::bail!("both tls_cert and tls_key must be set"),
+    };
     let payload_config = PayloadConfig::new(CONFIG.payload_size_limit.bytes() as usize);
+    // batch and transaction bodies
+    let json_config = JsonConfig::default().limit(CONFIG.payload_size_limit.bytes() as usize);
+
+    let health = Data::new(Health::new(POOL_SIZE));
+    let metrics = Data::new(metrics::Metrics::new(pool.clone(), POOL_SIZE)?);
+    let app_pool = pool.clone();
+    let app_health = health.clone();
+    let redirect = tls.is_some() && CONFIG.http_redirect;
 
     let server = HttpServer::new(move || {
         let cors = Cors::default()
@@ -193,11 +372,22 @@ async fn main() -> anyhow::Result<()> {
 
         App::new()
             .app_data(payload_config.clone())
-            .app_data(Data::new(pool.clone()))
+            .app_data(json_config.clone())
+            .app_data(Data::new(app_pool.clone()))
+            .app_data(app_health.clone())
+            .app_data(metrics.clone())
+            .wrap(middleware::from_fn(metrics::measure))
             .wrap(middleware::Logger::default())
+            .wrap(middleware::from_fn(telemetry::trace))
             .wrap(cors)
+            .wrap(middleware::Condition::new(
+                redirect,
+                middleware::from_fn(tls::redirect),
+            ))
             .service(
                 web::scope("/api")
+                    // the last wrap runs first: authenticate before reporting migrations
+                    .wrap(middleware::from_fn(health::migrated))
                     .wrap(middleware::from_fn(interceptor))
                     .route("/{bucket}", web::get().to(handlers::list))
                     .route("/{bucket}/{id}", web::get().to(handlers::get))
@@ -206,12 +396,25 @@ async fn main() -> anyhow::Result<()> {
             )
             .service(
                 web::scope("/api2")
+                    .wrap(middleware::from_fn(health::migrated))
                     .wrap(middleware::from_fn(interceptor))
+                    .route(
+                        "/{workspace}:transaction",
+                        web::post().to(handlers_v2::transaction),
+                    )
                     .route("/{workspace}/{bucket}", web::get().to(handlers_v2::list))
+                    .route(
+                        "/{workspace}/{bucket}:batchGet",
+                        web::post().to(handlers_v2::batch_get),
+                    )
                     .route(
                         "/{workspace}/{bucket}/{id}",
                         web::get().to(handlers_v2::get),
                     )
+                    .route(
+                        "/{workspace}/{bucket}/{id}",
+                        web::head().to(handlers_v2::head),
+                    )
                     .route(
                         "/{workspace}/{bucket}/{id}",
                         web::put().to(handlers_v2::put),
@@ -222,11 +425,51 @@ async fn main() -> anyhow::Result<()> {
                     ),
             )
             .route("/status", web::get().to(async || "ok"))
-    })
-    .bind(socket)?
+            .route("/live", web::get().to(health::live))
+            .route("/ready", web::get().to(health::ready))
+            .route("/metrics", web::get().to(metrics::metrics))
+    });
+
+    let server = match tls {
+        Some(config) => {
+            info!(%socket, "serving https");
+            let server = server.bind_rustls_0_23(socket, config)?;
+
+            match CONFIG.http_port {
+                Some(port) => {
+                    let socket = std::net::SocketAddr::new(socket.ip(), port);
+                    info!(%socket, redirect, "serving http");
+                    server.bind(socket)?
+                }
+                None => server,
+            }
+        }
+        None => server.bind(socket)?,
+    }
     .run();
 
-    server.await?;
+    let handle = server.handle();
+    let server = tokio::spawn(server);
+
+    // the server is up (and not ready) while the schema is being migrated
+    let migrated = match no_migrate {
+        false => migrate(&pool, &health).await,
+        true => wait_migrated(&pool, &health).await,
+    };
+
+    if let Err(error) = migrated {
+        handle.stop(false).await;
+        return Err(error);
+    }
+
+    health.set_migrations(Migrations::Done);
+    info!("ready");
+
+    server.await??;
+
+    if let Some(provider) = provider {
+        provider.shutdown()?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,324 @@
+//
+// Copyright © 2025 Hardcore Engineering Inc.
+//
+// Licensed under the Eclipse Public License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License. You may
+// obtain a copy of the License at https://www.eclipse.org/legal/epl-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use std::{collections::HashSet, sync::Mutex, time::Instant};
+
+use actix_web::{
+    Error, HttpResponse,
+    body::{BodySize, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::CONTENT_LENGTH,
+    middleware::Next,
+    web::Data,
+};
+use prometheus::{
+    Counter, Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+    core::{Collector, Desc},
+    proto::MetricFamily,
+};
+use tracing::error;
+
+use super::Pool;
+use crate::config::CONFIG;
+
+/// Label of workspaces beyond `metrics_workspaces`.
+const OTHER: &str = "other";
+
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    duration: HistogramVec,
+    bytes_read: IntCounterVec,
+    bytes_written: IntCounterVec,
+    precondition_failed: IntCounterVec,
+    workspace_requests: IntCounterVec,
+    workspaces: Mutex<HashSet<String>>,
+}
+
+impl Metrics {
+    pub fn new(pool: Pool, pool_size: u32) -> anyhow::Result<Self> {
+        let registry = Registry::new_custom(Some("hulykvs".to_owned()), None)?;
+
+        let route = &["method", "route", "status"];
+
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests served"),
+            route,
+        )?;
+
+        let duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time to serve an HTTP request",
+            ),
+            route,
+        )?;
+
+        let bytes_read = IntCounterVec::new(
+            Opts::new("http_request_bytes_total", "Request body bytes received"),
+            &["method", "route"],
+        )?;
+
+        let bytes_written = IntCounterVec::new(
+            Opts::new("http_response_bytes_total", "Response body bytes sent"),
+            &["method", "route"],
+        )?;
+
+        let precondition_failed = IntCounterVec::new(
+            Opts::new(
+                "precondition_failed_total",
+                "Conditional requests rejected with 412",
+            ),
+            &["method", "route"],
+        )?;
+
+        let workspace_requests = IntCounterVec::new(
+            Opts::new(
+                "workspace_requests_total",
+                "API requests per workspace, bounded by metrics_workspaces",
+            ),
+            &["workspace"],
+        )?;
+
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(duration.clone()))?;
+        registry.register(Box::new(bytes_read.clone()))?;
+        registry.register(Box::new(bytes_written.clone()))?;
+        registry.register(Box::new(precondition_failed.clone()))?;
+
+        if CONFIG.metrics_workspaces > 0 {
+            registry.register(Box::new(workspace_requests.clone()))?;
+        }
+
+        registry.register(Box::new(PoolCollector::new(pool, pool_size)?))?;
+
+        Ok(Self {
+            registry,
+            requests,
+            duration,
+            bytes_read,
+            bytes_written,
+            precondition_failed,
+            workspace_requests,
+            workspaces: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// The first `metrics_workspaces` workspaces seen get their own label.
+    fn workspace<'a>(&self, workspace: &'a str) -> &'a str {
+        let mut workspaces = self.workspaces.lock().unwrap();
+
+        if workspaces.contains(workspace) {
+            workspace
+        } else if workspaces.len() < CONFIG.metrics_workspaces {
+            workspaces.insert(workspace.to_owned());
+            workspace
+        } else {
+            OTHER
+        }
+    }
+}
+
+/// Prometheus text exposition of all metrics.
+pub async fn metrics(metrics: Data<Metrics>) -> HttpResponse {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+
+    match encoder.encode(&metrics.registry.gather(), &mut buffer) {
+        Ok(()) => HttpResponse::Ok()
+            .content_type(encoder.format_type())
+            .body(buffer),
+        Err(error) => {
+            error!(?error, "cannot encode metrics");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Records count, latency and sizes of every request, labelled by route pattern.
+pub async fn measure(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let Some(metrics) = request.app_data::<Data<Metrics>>().cloned() else {
+        return next.call(request).await;
+    };
+
+    let started = Instant::now();
+
+    let route = request
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_owned());
+    let method = request.method().as_str().to_owned();
+
+    // chunked bodies are not counted
+    let read = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(0);
+
+    let response = next.call(request).await;
+
+    let (status, written, workspace) = match &response {
+        Ok(response) => (
+            response.status(),
+            // HEAD bodies only carry the Content-Length of the value, nothing is sent
+            match response.response().body().size() {
+                BodySize::Sized(size) if method != "HEAD" => size,
+                _ => 0,
+            },
+            // path segments are resolved by routing
+            response.request().match_info().get("workspace"),
+        ),
+        Err(error) => (error.as_response_error().status_code(), 0, None),
+    };
+
+    metrics
+        .requests
+        .with_label_values(&[&method, &route, status.as_str()])
+        .inc();
+    metrics
+        .duration
+        .with_label_values(&[&method, &route, status.as_str()])
+        .observe(started.elapsed().as_secs_f64());
+    metrics
+        .bytes_read
+        .with_label_values(&[&method, &route])
+        .inc_by(read);
+    metrics
+        .bytes_written
+        .with_label_values(&[&method, &route])
+        .inc_by(written);
+
+    if status.as_u16() == 412 {
+        metrics
+            .precondition_failed
+            .with_label_values(&[&method, &route])
+            .inc();
+    }
+
+    // rejected tokens must not take up workspace labels
+    if let Some(workspace) = workspace
+        && CONFIG.metrics_workspaces > 0
+        && !matches!(status.as_u16(), 401 | 403)
+    {
+        metrics
+            .workspace_requests
+            .with_label_values(&[metrics.workspace(workspace)])
+            .inc();
+    }
+
+    response
+}
+
+/// bb8 pool state, read on every scrape.
+struct PoolCollector {
+    pool: Pool,
+    pool_size: u32,
+    descs: Vec<Desc>,
+}
+
+impl PoolCollector {
+    const METRICS: [(&str, &str); 8] = [
+        ("pool_max_size", "Maximum number of connections"),
+        ("pool_connections", "Open connections"),
+        ("pool_idle_connections", "Idle connections"),
+        ("pool_in_use_connections", "Connections checked out"),
+        ("pool_get_total", "Connection checkouts"),
+        (
+            "pool_get_waited_total",
+            "Checkouts which waited for a connection",
+        ),
+        ("pool_get_timed_out_total", "Checkouts which timed out"),
+        (
+            "pool_get_wait_seconds_total",
+            "Time spent waiting for a connection",
+        ),
+    ];
+
+    fn new(pool: Pool, pool_size: u32) -> anyhow::Result<Self> {
+        let descs = Self::METRICS
+            .iter()
+            .map(|(name, help)| {
+                Desc::new(
+                    name.to_string(),
+                    help.to_string(),
+                    Vec::new(),
+                    Default::default(),
+                )
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            pool,
+            pool_size,
+            descs,
+        })
+    }
+}
+
+impl Collector for PoolCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.descs.iter().collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let state = self.pool.state();
+        let statistics = &state.statistics;
+
+        let gauges = [
+            self.pool_size,
+            state.connections,
+            state.idle_connections,
+            state.connections - state.idle_connections,
+        ];
+
+        let counters = [
+            statistics.get_direct + statistics.get_waited + statistics.get_timed_out,
+            statistics.get_waited,
+            statistics.get_timed_out,
+        ];
+
+        let mut families = Vec::new();
+        let mut metrics = Self::METRICS.iter();
+
+        // names are prefixed by the registry
+        for (value, (name, help)) in gauges.into_iter().zip(metrics.by_ref()) {
+            if let Ok(gauge) = IntGauge::new(*name, *help) {
+                gauge.set(value.into());
+                families.extend(gauge.collect());
+            }
+        }
+
+        for (value, (name, help)) in counters.into_iter().zip(metrics.by_ref()) {
+            if let Ok(counter) = IntCounter::new(*name, *help) {
+                counter.inc_by(value);
+                families.extend(counter.collect());
+            }
+        }
+
+        if let Some((name, help)) = metrics.next()
+            && let Ok(counter) = Counter::new(*name, *help)
+        {
+            counter.inc_by(statistics.get_wait_time.as_secs_f64());
+            families.extend(counter.collect());
+        }
+
+        families
+    }
+}
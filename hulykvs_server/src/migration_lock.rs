@@ -0,0 +1,174 @@
+//
+// Copyright © 2025 Hardcore Engineering Inc.
+//
+// Licensed under the Eclipse Public License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License. You may
+// obtain a copy of the License at https://www.eclipse.org/legal/epl-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Serializes migrations of instances sharing a database.
+//!
+//! PostgreSQL: session advisory lock, released when the migrating connection
+//! closes. CockroachDB has no advisory locks, a row in `migration_lock` is held
+//! instead and kept alive by a heartbeat, so the lock of a crashed instance
+//! is taken over once it goes stale.
+
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use tokio_postgres as pg;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use super::{DbBackend, Pool};
+use crate::config::CONFIG;
+
+/// First key of the advisory lock, the second one is the hash of the schema.
+const LOCK_CLASS: i32 = 0x6b7673; // "kvs"
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A lock row without heartbeat for this long belongs to a dead instance.
+const STALE_AFTER: Duration = Duration::from_secs(60);
+
+pub enum MigrationLock {
+    Advisory,
+    Row {
+        owner: String,
+        heartbeat: JoinHandle<()>,
+    },
+}
+
+impl MigrationLock {
+    /// Waits until no other instance migrates the schema.
+    pub async fn acquire(
+        connection: &pg::Client,
+        pool: &Pool,
+        backend: DbBackend,
+    ) -> anyhow::Result<Self> {
+        match backend {
+            DbBackend::Postgres => {
+                let statement = "select pg_try_advisory_lock($1, hashtext($2))";
+
+                poll(async || {
+                    let row = connection
+                        .query_one(statement, &[&LOCK_CLASS, &CONFIG.db_scheme])
+                        .await?;
+                    Ok(row.get::<_, bool>(0))
+                })
+                .await?;
+
+                Ok(MigrationLock::Advisory)
+            }
+
+            DbBackend::Cockroach => {
+                connection
+                    .execute(
+                        r#"
+                        create table if not exists migration_lock (
+                            id int primary key,
+                            owner text not null,
+                            heartbeat timestamptz not null
+                        )
+                        "#,
+                        &[],
+                    )
+                    .await?;
+
+                let owner = Uuid::new_v4().to_string();
+
+                let statement = r#"
+                    insert into migration_lock (id, owner, heartbeat) values (1, $1, now())
+                    on conflict (id) do update set owner = excluded.owner, heartbeat = now()
+                    where migration_lock.heartbeat < now() - interval '1 second' * $2
+                "#;
+
+                let stale = STALE_AFTER.as_secs_f64();
+
+                poll(async || Ok(connection.execute(statement, &[&owner, &stale]).await? == 1))
+                    .await?;
+
+                let heartbeat = tokio::spawn(heartbeat(pool.clone(), owner.clone()));
+
+                Ok(MigrationLock::Row { owner, heartbeat })
+            }
+        }
+    }
+
+    pub async fn release(self, connection: &pg::Client) -> anyhow::Result<()> {
+        match self {
+            MigrationLock::Advisory => {
+                connection
+                    .query_one(
+                        "select pg_advisory_unlock($1, hashtext($2))",
+                        &[&LOCK_CLASS, &CONFIG.db_scheme],
+                    )
+                    .await?;
+            }
+
+            MigrationLock::Row { owner, heartbeat } => {
+                heartbeat.abort();
+
+                connection
+                    .execute(
+                        "delete from migration_lock where id = 1 and owner = $1",
+                        &[&owner],
+                    )
+                    .await?;
+            }
+        }
+
+        info!("released migration lock");
+
+        Ok(())
+    }
+}
+
+async fn poll(mut try_lock: impl AsyncFnMut() -> anyhow::Result<bool>) -> anyhow::Result<()> {
+    let mut waiting = false;
+
+    while !try_lock().await? {
+        if !waiting {
+            info!("another instance is migrating the database, waiting");
+            waiting = true;
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+
+    info!("acquired migration lock");
+
+    Ok(())
+}
+
+async fn heartbeat(pool: Pool, owner: String) {
+    let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let result = async {
+            let connection = pool.get().await?;
+            connection
+                .execute(
+                    "update migration_lock set heartbeat = now() where id = 1 and owner = $1",
+                    &[&owner],
+                )
+                .await?;
+            anyhow::Ok(())
+        }
+        .await;
+
+        if let Err(error) = result {
+            error!(?error, "cannot refresh migration lock");
+        }
+    }
+}
@@ -0,0 +1,414 @@
+//
+// Copyright © 2025 Hardcore Engineering Inc.
+//
+// Licensed under the Eclipse Public License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License. You may
+// obtain a copy of the License at https://www.eclipse.org/legal/epl-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Namespace access control.
+//!
+//! Access is granted by rules of the policy file (`policy_file`), matched against
+//! the token claims, and by the `kvs` claim of the token itself, a comma separated
+//! list of `<permissions>:<namespace pattern>` such as `rl:public*,rwl:github`.
+//! Without either, every namespace of the workspace is accessible.
+
+use std::{collections::HashMap, fmt, sync::LazyLock};
+
+use actix_web::{Error, HttpMessage, HttpRequest, error};
+use hulyrs::services::jwt::Claims;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::config::CONFIG;
+
+/// Token claim (in `extra`) with namespace grants.
+const GRANTS_CLAIM: &str = "kvs";
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    Read,
+    Write,
+    List,
+}
+
+impl Permission {
+    fn parse(letter: char) -> Option<Self> {
+        match letter {
+            'r' => Some(Permission::Read),
+            'w' => Some(Permission::Write),
+            'l' => Some(Permission::List),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Permission::Read => "read",
+            Permission::Write => "write",
+            Permission::List => "list",
+        })
+    }
+}
+
+/// Grants `permissions` on `namespaces` to tokens matching all of the given claims.
+#[derive(Deserialize, Debug)]
+pub struct Rule {
+    pub account: Option<Uuid>,
+    /// `extra.service` of the token
+    pub service: Option<String>,
+    #[serde(default)]
+    pub extra: HashMap<String, String>,
+
+    /// Patterns, `*` matches any sequence of characters
+    pub namespaces: Vec<String>,
+    pub permissions: Vec<Permission>,
+}
+
+impl Rule {
+    fn applies(&self, claims: &Claims) -> bool {
+        let extra = |name: &str| claims.extra.as_ref().and_then(|extra| extra.get(name));
+
+        self.account.is_none_or(|account| claims.account == account)
+            && self
+                .service
+                .as_ref()
+                .is_none_or(|service| extra("service") == Some(service))
+            && self
+                .extra
+                .iter()
+                .all(|(name, value)| extra(name) == Some(value))
+    }
+
+    fn grants(&self, namespace: &str, permission: Permission) -> bool {
+        self.permissions.contains(&permission)
+            && self
+                .namespaces
+                .iter()
+                .any(|pattern| matches(pattern, namespace))
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Policy {
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+pub static POLICY: LazyLock<Option<Policy>> = LazyLock::new(|| {
+    let path = CONFIG.policy_file.as_ref()?;
+
+    let policy = config::Config::builder()
+        .add_source(config::File::from(path.as_path()))
+        .build()
+        .and_then(|c| c.try_deserialize::<Policy>());
+
+    match policy {
+        Ok(policy) => Some(policy),
+        Err(error) => {
+            eprintln!("policy error ({}): {}", path.display(), error);
+            std::process::exit(1);
+        }
+    }
+});
+
+/// Glob match where `*` stands for any (possibly empty) sequence of characters.
+fn matches(pattern: &str, namespace: &str) -> bool {
+    let mut parts = pattern.split('*');
+
+    // no '*': exact match
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = namespace.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts = parts.peekable();
+
+    if parts.peek().is_none() {
+        return rest.is_empty();
+    }
+
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return rest.ends_with(part);
+        }
+
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    true
+}
+
+/// Permissions on a namespace pattern.
+type Grant<'a> = (Vec<Permission>, &'a str);
+
+/// Grants of the `kvs` claim, `None` if the token has none.
+fn claim_grants(claims: &Claims) -> Result<Option<Vec<Grant<'_>>>, Error> {
+    let Some(value) = claims
+        .extra
+        .as_ref()
+        .and_then(|extra| extra.get(GRANTS_CLAIM))
+    else {
+        return Ok(None);
+    };
+
+    let malformed = || error::ErrorForbidden(format!("Malformed {GRANTS_CLAIM} claim"));
+
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|grant| !grant.is_empty())
+        .map(|grant| {
+            let (letters, pattern) = grant.split_once(':').ok_or_else(malformed)?;
+
+            let permissions = letters
+                .chars()
+                .map(Permission::parse)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(malformed)?;
+
+            Ok((permissions, pattern))
+        })
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+/// Checks `permission` on the namespace of the request path (`{bucket}`).
+pub fn authorize(req: &HttpRequest, permission: Permission) -> Result<(), Error> {
+    let namespace = req
+        .match_info()
+        .get("bucket")
+        .ok_or_else(|| error::ErrorBadRequest("Missing namespace in URL path"))?;
+
+    authorize_namespace(req, namespace, permission)
+}
+
+/// Checks `permission` on `namespace` for the token of the request.
+pub fn authorize_namespace(
+    req: &HttpRequest,
+    namespace: &str,
+    permission: Permission,
+) -> Result<(), Error> {
+    let extensions = req.extensions();
+
+    let claims = extensions
+        .get::<Claims>()
+        .ok_or_else(|| error::ErrorUnauthorized("Missing auth claims"))?;
+
+    check(claims, POLICY.as_ref(), namespace, permission)
+}
+
+/// Grants of the `kvs` claim of `claims` merged with the rules of `policy`.
+fn check(
+    claims: &Claims,
+    policy: Option<&Policy>,
+    namespace: &str,
+    permission: Permission,
+) -> Result<(), Error> {
+    // is_system - allowed to all
+    if claims.is_system() {
+        return Ok(());
+    }
+
+    let grants = claim_grants(claims)?;
+
+    if grants.is_none() && policy.is_none() {
+        return Ok(());
+    }
+
+    let by_token = grants.iter().flatten().any(|(permissions, pattern)| {
+        permissions.contains(&permission) && matches(pattern, namespace)
+    });
+
+    let by_policy = policy
+        .iter()
+        .flat_map(|policy| &policy.rules)
+        .any(|rule| rule.applies(claims) && rule.grants(namespace, permission));
+
+    if by_token || by_policy {
+        return Ok(());
+    }
+
+    let source = match (grants.is_some(), policy.is_some()) {
+        (true, true) => "token or policy",
+        (true, false) => "token",
+        _ => "policy",
+    };
+
+    Err(error::ErrorForbidden(format!(
+        "No {permission} access to namespace {namespace}: not granted by {source}"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(service: &str, kvs: Option<&str>) -> Claims {
+        let mut extra = serde_json::json!({ "service": service });
+
+        if let Some(kvs) = kvs {
+            extra[GRANTS_CLAIM] = kvs.into();
+        }
+
+        serde_json::from_value(serde_json::json!({
+            "account": "aaaaaaaa-bbbb-cccc-dddd-eeeeeeeeeeee",
+            "workspace": "00000000-0000-0000-0000-000000000001",
+            "extra": extra,
+        }))
+        .unwrap()
+    }
+
+    fn policy(toml: &str) -> Policy {
+        config::Config::builder()
+            .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+            .build()
+            .and_then(|c| c.try_deserialize())
+            .unwrap()
+    }
+
+    const POLICY_FILE: &str = r#"
+        [[rules]]
+        service = "github"
+        namespaces = ["github", "github-*"]
+        permissions = ["read", "write", "list"]
+
+        [[rules]]
+        namespaces = ["public*"]
+        permissions = ["read", "list"]
+    "#;
+
+    /// Error message, `None` if granted.
+    fn denied(
+        claims: &Claims,
+        policy: Option<&Policy>,
+        namespace: &str,
+        permission: Permission,
+    ) -> Option<String> {
+        check(claims, policy, namespace, permission)
+            .err()
+            .map(|error| error.to_string())
+    }
+
+    #[test]
+    fn patterns() {
+        assert!(matches("github", "github"));
+        assert!(!matches("github", "github-1"));
+        assert!(!matches("github", "git"));
+        assert!(matches("*", ""));
+        assert!(matches("*", "any"));
+        assert!(matches("public*", "public"));
+        assert!(matches("public*", "public1"));
+        assert!(!matches("public*", "pub"));
+        assert!(matches("*-logs", "app-logs"));
+        assert!(!matches("*-logs", "app-logs-old"));
+        assert!(matches("a*b*c", "abc"));
+        assert!(matches("a*b*c", "a-b-b-c"));
+        assert!(!matches("a*b*c", "a-c-b"));
+        assert!(!matches("a*a", "a"));
+        assert!(matches("a**", "a"));
+        assert!(!matches("", "a"));
+    }
+
+    #[test]
+    fn no_rules_allow_everything() {
+        let claims = claims("account", None);
+
+        for permission in [Permission::Read, Permission::Write, Permission::List] {
+            assert_eq!(denied(&claims, None, "any", permission), None);
+        }
+    }
+
+    #[test]
+    fn claim_only() {
+        let claims = claims("account", Some("rl:public*, rwl:github"));
+
+        assert_eq!(denied(&claims, None, "public1", Permission::Read), None);
+        assert_eq!(denied(&claims, None, "public1", Permission::List), None);
+        assert_eq!(
+            denied(&claims, None, "public1", Permission::Write).as_deref(),
+            Some("No write access to namespace public1: not granted by token")
+        );
+        assert_eq!(denied(&claims, None, "github", Permission::Write), None);
+        assert_eq!(
+            denied(&claims, None, "private", Permission::Read).as_deref(),
+            Some("No read access to namespace private: not granted by token")
+        );
+
+        let malformed = self::claims("account", Some("rx:github"));
+        assert_eq!(
+            denied(&malformed, None, "github", Permission::Read).as_deref(),
+            Some("Malformed kvs claim")
+        );
+    }
+
+    #[test]
+    fn file_only() {
+        let policy = policy(POLICY_FILE);
+        let github = claims("github", None);
+        let other = claims("account", None);
+
+        assert_eq!(
+            denied(&github, Some(&policy), "github-1", Permission::Write),
+            None
+        );
+        assert_eq!(
+            denied(&other, Some(&policy), "github", Permission::Read).as_deref(),
+            Some("No read access to namespace github: not granted by policy")
+        );
+
+        // the rule without selectors applies to every token
+        assert_eq!(
+            denied(&other, Some(&policy), "public1", Permission::List),
+            None
+        );
+        assert!(denied(&other, Some(&policy), "public1", Permission::Write).is_some());
+    }
+
+    #[test]
+    fn claim_and_file_merge() {
+        let policy = policy(POLICY_FILE);
+        let claims = claims("account", Some("w:public1"));
+
+        // write from the claim, read and list from the policy
+        for permission in [Permission::Read, Permission::Write, Permission::List] {
+            assert_eq!(denied(&claims, Some(&policy), "public1", permission), None);
+        }
+
+        assert_eq!(
+            denied(&claims, Some(&policy), "public2", Permission::Write).as_deref(),
+            Some("No write access to namespace public2: not granted by token or policy")
+        );
+    }
+
+    #[test]
+    fn permissions_are_separate() {
+        for (letters, granted) in [
+            ("r", Permission::Read),
+            ("w", Permission::Write),
+            ("l", Permission::List),
+        ] {
+            let claims = claims("account", Some(&format!("{letters}:ns")));
+
+            for permission in [Permission::Read, Permission::Write, Permission::List] {
+                assert_eq!(
+                    denied(&claims, None, "ns", permission).is_none(),
+                    permission == granted
+                );
+            }
+        }
+    }
+}
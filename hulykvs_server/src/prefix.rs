@@ -0,0 +1,88 @@
+//
+// Copyright © 2025 Hardcore Engineering Inc.
+//
+// Licensed under the Eclipse Public License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License. You may
+// obtain a copy of the License at https://www.eclipse.org/legal/epl-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Literal prefix matching as a key range, `key >= prefix and key < successor(prefix)`.
+//!
+//! Keys compare bytewise (`COLLATE "C"` on PostgreSQL, the default on CockroachDB),
+//! and the byte order of UTF-8 is the code point order, so every key starting
+//! with the prefix falls in the range.
+
+/// Smallest string greater than every string starting with `prefix`,
+/// `None` if there is no such string (empty prefix or only `char::MAX`).
+pub fn successor(prefix: &str) -> Option<String> {
+    let mut chars: Vec<char> = prefix.chars().collect();
+
+    while let Some(last) = chars.pop() {
+        let next = (last as u32 + 1..=char::MAX as u32).find_map(char::from_u32);
+
+        if let Some(next) = next {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_range(prefix: &str, key: &str) -> bool {
+        key >= prefix && successor(prefix).is_none_or(|end| key < end.as_str())
+    }
+
+    #[test]
+    fn special_characters() {
+        // LIKE wildcards are plain characters here
+        assert_eq!(successor("a%").as_deref(), Some("a&"));
+        assert_eq!(successor("a_").as_deref(), Some("a`"));
+        assert!(in_range("a%", "a%b"));
+        assert!(!in_range("a%", "ab"));
+        assert!(!in_range("a_", "ab"));
+    }
+
+    #[test]
+    fn multi_byte_characters() {
+        assert_eq!(successor("ключ").as_deref(), Some("клюш"));
+        assert_eq!(successor("a\u{7f}").as_deref(), Some("a\u{80}"));
+        assert_eq!(successor("🔑").as_deref(), Some("🔒"));
+        assert!(in_range("ключ", "ключи"));
+        assert!(in_range("a\u{7f}", "a\u{7f}\u{10ffff}"));
+        assert!(!in_range("a\u{7f}", "a\u{80}"));
+    }
+
+    #[test]
+    fn surrogate_gap() {
+        assert_eq!(successor("a\u{d7ff}").as_deref(), Some("a\u{e000}"));
+        assert!(in_range("a\u{d7ff}", "a\u{d7ff}z"));
+        assert!(!in_range("a\u{d7ff}", "a\u{e000}"));
+    }
+
+    #[test]
+    fn trailing_max() {
+        assert_eq!(successor("a\u{10ffff}").as_deref(), Some("b"));
+        assert_eq!(successor("a\u{10ffff}\u{10ffff}").as_deref(), Some("b"));
+        assert_eq!(successor("\u{10ffff}"), None);
+        assert!(in_range("a\u{10ffff}", "a\u{10ffff}\u{10ffff}x"));
+        assert!(!in_range("a\u{10ffff}", "b"));
+    }
+
+    #[test]
+    fn empty_prefix() {
+        assert_eq!(successor(""), None);
+        assert!(in_range("", "anything"));
+    }
+}
@@ -0,0 +1,127 @@
+//
+// Copyright © 2025 Hardcore Engineering Inc.
+//
+// Licensed under the Eclipse Public License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License. You may
+// obtain a copy of the License at https://www.eclipse.org/legal/epl-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Embedded migrations compared with those applied to the database.
+
+use refinery::Migration;
+use tokio_postgres as pg;
+
+use super::{DbBackend, migrations_crdb, migrations_pg};
+use crate::config::CONFIG;
+
+/// Migration history table of the backend.
+pub fn table(backend: DbBackend) -> &'static str {
+    match backend {
+        DbBackend::Cockroach => "migrations",
+        DbBackend::Postgres => "migrations_pg",
+    }
+}
+
+/// Embedded migrations in order of version.
+pub fn embedded(backend: DbBackend) -> Vec<Migration> {
+    let mut migrations = match backend {
+        DbBackend::Cockroach => migrations_crdb::migrations::runner()
+            .get_migrations()
+            .clone(),
+        DbBackend::Postgres => migrations_pg::migrations::runner().get_migrations().clone(),
+    };
+
+    migrations.sort_by_key(|m| m.version());
+    migrations
+}
+
+/// Applied migrations, `None` if the history table does not exist.
+pub async fn applied(
+    connection: &mut pg::Client,
+    backend: DbBackend,
+) -> anyhow::Result<Option<Vec<Migration>>> {
+    let exists = connection
+        .query_opt(
+            "select 1 from information_schema.tables where table_schema = $1 and table_name = $2",
+            &[&CONFIG.db_scheme, &table(backend)],
+        )
+        .await?
+        .is_some();
+
+    if !exists {
+        return Ok(None);
+    }
+
+    let applied = match backend {
+        DbBackend::Cockroach => {
+            migrations_crdb::migrations::runner()
+                .set_migration_table_name(table(backend))
+                .get_applied_migrations_async(connection)
+                .await?
+        }
+        DbBackend::Postgres => {
+            migrations_pg::migrations::runner()
+                .set_migration_table_name(table(backend))
+                .get_applied_migrations_async(connection)
+                .await?
+        }
+    };
+
+    Ok(Some(applied))
+}
+
+/// Embedded migrations not applied to the database yet.
+pub async fn pending(
+    connection: &mut pg::Client,
+    backend: DbBackend,
+) -> anyhow::Result<Vec<Migration>> {
+    let applied = applied(connection, backend).await?.unwrap_or_default();
+
+    Ok(embedded(backend)
+        .into_iter()
+        .filter(|m| !applied.iter().any(|a| a.version() == m.version()))
+        .collect())
+}
+
+#[derive(Debug)]
+pub enum Mismatch {
+    /// Applied with another checksum than the embedded migration
+    Divergent(Migration),
+    /// Applied, but not embedded in this build
+    Missing(Migration),
+}
+
+/// Compares applied migrations of `backend` with the embedded ones,
+/// `None` if the history table does not exist.
+pub async fn verify(
+    connection: &mut pg::Client,
+    backend: DbBackend,
+) -> anyhow::Result<Option<Vec<Mismatch>>> {
+    let Some(applied) = applied(connection, backend).await? else {
+        return Ok(None);
+    };
+
+    let embedded = embedded(backend);
+
+    let mismatches = applied
+        .into_iter()
+        .filter_map(
+            |a| match embedded.iter().find(|m| m.version() == a.version()) {
+                None => Some(Mismatch::Missing(a)),
+                Some(m) if m.checksum() != a.checksum() || m.name() != a.name() => {
+                    Some(Mismatch::Divergent(a))
+                }
+                Some(_) => None,
+            },
+        )
+        .collect();
+
+    Ok(Some(mismatches))
+}
@@ -0,0 +1,201 @@
+//
+// Copyright © 2025 Hardcore Engineering Inc.
+//
+// Licensed under the Eclipse Public License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License. You may
+// obtain a copy of the License at https://www.eclipse.org/legal/epl-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use actix_web::{
+    Error,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::{Method, header::HeaderMap},
+    middleware::Next,
+};
+use opentelemetry::{
+    KeyValue, global,
+    propagation::Extractor,
+    trace::{TraceContextExt, TracerProvider as _},
+};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{Resource, propagation::TraceContextPropagator, trace::SdkTracerProvider};
+use tracing::{Instrument, Span, field::Empty, info_span};
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::config::CONFIG;
+
+/// OTLP/HTTP exporter for `otel_endpoint`, `None` if it is not configured.
+pub fn provider() -> anyhow::Result<Option<SdkTracerProvider>> {
+    let Some(endpoint) = &CONFIG.otel_endpoint else {
+        return Ok(None);
+    };
+
+    let endpoint = endpoint.trim_end_matches('/');
+    let endpoint = match endpoint.ends_with("/v1/traces") {
+        true => endpoint.to_owned(),
+        false => format!("{endpoint}/v1/traces"),
+    };
+
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()?;
+
+    let resource = Resource::builder()
+        .with_service_name(CONFIG.otel_service_name.clone())
+        .with_attribute(KeyValue::new("service.version", env!("CARGO_PKG_VERSION")))
+        .build();
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource)
+        .build();
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider.clone());
+
+    Ok(Some(provider))
+}
+
+pub fn layer<S>(
+    provider: &SdkTracerProvider,
+) -> OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(env!("CARGO_BIN_NAME")))
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+fn op(method: &Method, route: &str, key: bool) -> &'static str {
+    match (method.as_str(), key) {
+        ("POST", _) if route.ends_with(":batchGet") => "batch_get",
+        ("POST", _) if route.ends_with(":transaction") => "transaction",
+        ("GET", true) => "get",
+        ("GET", false) => "list",
+        ("HEAD", _) => "head",
+        ("PUT" | "POST", _) => "update",
+        ("DELETE", _) => "delete",
+        _ => "other",
+    }
+}
+
+/// Wraps every request in a span, continuing the trace of an incoming `traceparent`.
+pub async fn trace(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let route = request
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_owned());
+    let method = request.method().clone();
+
+    let span = info_span!(
+        "request",
+        otel.name = format!("{method} {route}"),
+        otel.kind = "server",
+        otel.status_code = Empty,
+        http.request.method = %method,
+        http.route = route,
+        http.response.status_code = Empty,
+        op = Empty,
+        workspace = Empty,
+        namespace = Empty,
+        key = Empty,
+    );
+
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+
+    if parent.span().span_context().is_valid() {
+        let _ = span.set_parent(parent);
+    }
+
+    let response = next.call(request).instrument(span.clone()).await;
+
+    if let Ok(response) = &response {
+        let info = response.request().match_info();
+
+        // path segments are named after the v1 API
+        for (field, segment) in [
+            ("workspace", "workspace"),
+            ("namespace", "bucket"),
+            ("key", "id"),
+        ] {
+            if let Some(value) = info.get(segment) {
+                span.record(field, value);
+            }
+        }
+
+        span.record("op", op(&method, &route, info.get("id").is_some()));
+
+        let status = response.status();
+        span.record("http.response.status_code", status.as_u16());
+
+        if status.is_server_error() {
+            span.record("otel.status_code", "ERROR");
+        }
+    } else {
+        span.record("otel.status_code", "ERROR");
+    }
+
+    response
+}
+
+/// Span of a database round trip, its duration is the DB timing of the request.
+pub fn db(operation: &'static str) -> Span {
+    info_span!(
+        "db",
+        otel.name = format!("db {operation}"),
+        otel.kind = "client",
+        db.system = "postgresql",
+        db.operation = operation,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ops() {
+        let key = "/api2/{workspace}/{bucket}/{id}";
+        let namespace = "/api2/{workspace}/{bucket}";
+
+        assert_eq!(op(&Method::GET, key, true), "get");
+        assert_eq!(op(&Method::HEAD, key, true), "head");
+        assert_eq!(op(&Method::PUT, key, true), "update");
+        assert_eq!(op(&Method::POST, "/api/{bucket}/{id}", true), "update");
+        assert_eq!(op(&Method::DELETE, key, true), "delete");
+        assert_eq!(op(&Method::GET, namespace, false), "list");
+        assert_eq!(
+            op(&Method::POST, "/api2/{workspace}/{bucket}:batchGet", false),
+            "batch_get"
+        );
+        assert_eq!(
+            op(&Method::POST, "/api2/{workspace}:transaction", false),
+            "transaction"
+        );
+    }
+}
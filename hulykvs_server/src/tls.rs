@@ -0,0 +1,183 @@
+//
+// Copyright © 2025 Hardcore Engineering Inc.
+//
+// Licensed under the Eclipse Public License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License. You may
+// obtain a copy of the License at https://www.eclipse.org/legal/epl-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+//
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use actix_web::{
+    Error, HttpResponse,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::LOCATION,
+    middleware::Next,
+};
+use anyhow::Context;
+use rustls::{
+    ServerConfig,
+    crypto::{CryptoProvider, ring},
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use tracing::{error, info};
+
+use crate::config::CONFIG;
+
+/// How often certificate files are checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Serves the certificate from PEM files, replaced when the files change.
+#[derive(Debug)]
+pub struct CertificateResolver {
+    cert: PathBuf,
+    key: PathBuf,
+    provider: Arc<CryptoProvider>,
+    current: RwLock<Arc<CertifiedKey>>,
+    modified: Mutex<(SystemTime, SystemTime)>,
+}
+
+impl CertificateResolver {
+    pub fn new(cert: &Path, key: &Path, provider: Arc<CryptoProvider>) -> anyhow::Result<Self> {
+        let modified = (mtime(cert)?, mtime(key)?);
+        let current = load(cert, key, &provider)?;
+
+        Ok(Self {
+            cert: cert.to_owned(),
+            key: key.to_owned(),
+            provider,
+            current: RwLock::new(Arc::new(current)),
+            modified: Mutex::new(modified),
+        })
+    }
+
+    /// Reloads the certificate if either file has changed since the last load.
+    /// The current certificate stays in use if the new files can not be loaded.
+    pub fn reload(&self) -> anyhow::Result<bool> {
+        let modified = (mtime(&self.cert)?, mtime(&self.key)?);
+
+        let mut last = self.modified.lock().unwrap();
+
+        if *last == modified {
+            return Ok(false);
+        }
+
+        let certified = load(&self.cert, &self.key, &self.provider)?;
+
+        *self.current.write().unwrap() = Arc::new(certified);
+        *last = modified;
+
+        Ok(true)
+    }
+
+    /// Polls certificate files in the background.
+    pub fn watch(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+
+            loop {
+                interval.tick().await;
+
+                match self.reload() {
+                    Ok(true) => info!(cert = %self.cert.display(), "reloaded tls certificate"),
+                    Ok(false) => {}
+                    Err(error) => error!(?error, "cannot reload tls certificate"),
+                }
+            }
+        });
+    }
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, _: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+fn mtime(path: &Path) -> anyhow::Result<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .with_context(|| format!("cannot stat {}", path.display()))
+}
+
+fn load(cert: &Path, key: &Path, provider: &CryptoProvider) -> anyhow::Result<CertifiedKey> {
+    let chain = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("cannot read certificates from {}", cert.display()))?;
+
+    if chain.is_empty() {
+        anyhow::bail!("no certificates in {}", cert.display());
+    }
+
+    let key = PrivateKeyDer::from_pem_file(key)
+        .with_context(|| format!("cannot read private key from {}", key.display()))?;
+
+    Ok(CertifiedKey::from_der(chain, key, provider)?)
+}
+
+/// Server configuration for `tls_cert` and `tls_key`, reloaded on file change.
+pub fn server_config(cert: &Path, key: &Path) -> anyhow::Result<ServerConfig> {
+    let provider = Arc::new(ring::default_provider());
+
+    let resolver = Arc::new(CertificateResolver::new(cert, key, provider.clone())?);
+    resolver.clone().watch();
+
+    let mut config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver);
+
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(config)
+}
+
+/// Redirects plain HTTP requests to the TLS listener, probes and metrics are served on both.
+pub async fn redirect(
+    request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let probe = matches!(request.path(), "/status" | "/live" | "/ready" | "/metrics");
+
+    if request.app_config().secure() || probe {
+        return Ok(next.call(request).await?.map_into_left_body());
+    }
+
+    let host = request.connection_info().host().to_owned();
+    let host = match host.rsplit_once(':') {
+        // keep IPv6 literals intact
+        Some((name, port)) if !port.contains(']') => name.to_owned(),
+        _ => host,
+    };
+
+    let path = request
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+
+    let location = match CONFIG.bind_port {
+        443 => format!("https://{host}{path}"),
+        port => format!("https://{host}:{port}{path}"),
+    };
+
+    let response = HttpResponse::PermanentRedirect()
+        .insert_header((LOCATION, location))
+        .finish();
+
+    Ok(request.into_response(response).map_into_right_body())
+}
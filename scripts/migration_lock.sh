@@ -0,0 +1,81 @@
+#!/bin/bash
+
+# Checks that instances sharing a database migrate one at a time: two instances
+# started at once on a fresh schema both succeed and only one applies migrations;
+# an instance waits while the lock is held, and on CockroachDB takes over a stale
+# lock row.
+# Usage: ./migration_lock.sh [connection string] (default: local CockroachDB as in db_view.sh)
+# The server binary is $HULYKVS_BIN, ../target/debug/hulykvs by default.
+
+CN="${1:-postgresql://root@huly.local:26257/defaultdb?sslmode=disable}"
+BIN="${HULYKVS_BIN:-../target/debug/hulykvs}"
+SCHEME="hulykvs_lock_test"
+LOGS=$(mktemp -d)
+
+R='\033[0;31m' # Color red
+G='\033[0;32m' # Color green
+N='\033[0m' # No Color
+
+export HULY_DB_CONNECTION="${CN}"
+export HULY_DB_SCHEME="${SCHEME}"
+export PGOPTIONS="--client-min-messages=warning"
+
+FAILED=0
+
+check() {
+  if eval "$2"; then echo -e "${G}OK: $1${N}"
+  else echo -e "${R}FAIL: $1${N}"; FAILED=1
+  fi
+}
+
+if psql "$CN" -Atc "select version()" | grep -q CockroachDB; then
+  CRDB=1
+  TABLE="migrations"
+else
+  CRDB=0
+  TABLE="migrations_pg"
+fi
+
+psql "$CN" -qc "drop schema if exists ${SCHEME} cascade"
+
+echo "-- two instances migrating a fresh schema at once"
+"$BIN" migrate > "${LOGS}/a.log" 2>&1 & A=$!
+"$BIN" migrate > "${LOGS}/b.log" 2>&1 & B=$!
+wait $A; RA=$?
+wait $B; RB=$?
+
+check "both instances succeed" '[ $RA -eq 0 ] && [ $RB -eq 0 ]'
+APPLIED=$(grep -l "applied migration" "${LOGS}/a.log" "${LOGS}/b.log" | wc -l)
+check "one instance applies migrations" '[ "$APPLIED" -eq 1 ]'
+HISTORY=$(psql "$CN" -Atc "select count(*) = count(distinct version) and count(*) > 0 from ${SCHEME}.${TABLE}")
+check "every migration is applied once" '[ "$HISTORY" == "t" ]'
+
+echo "-- an instance waits while the lock is held"
+if [ $CRDB -eq 1 ]; then
+  psql "$CN" -qc "upsert into ${SCHEME}.migration_lock (id, owner, heartbeat) values (1, 'alive', now())"
+else
+  # "kvs" lock class, held by a session for a while
+  psql "$CN" -qc "select pg_advisory_lock(7042675, hashtext('${SCHEME}')), pg_sleep(8)" > /dev/null &
+  HOLDER=$!
+  sleep 1
+fi
+
+timeout 5 "$BIN" migrate > "${LOGS}/c.log" 2>&1; RC=$?
+check "the instance is still waiting after 5 seconds" '[ $RC -eq 124 ]'
+check "the instance reports the wait" 'grep -q "another instance is migrating" "${LOGS}/c.log"'
+
+if [ $CRDB -eq 1 ]; then
+  echo "-- a stale lock row is taken over"
+  psql "$CN" -qc "update ${SCHEME}.migration_lock set heartbeat = now() - interval '2 minutes' where id = 1"
+  timeout 20 "$BIN" migrate > "${LOGS}/d.log" 2>&1; RD=$?
+  check "the instance takes the lock over and succeeds" '[ $RD -eq 0 ]'
+  LOCKS=$(psql "$CN" -Atc "select count(*) from ${SCHEME}.migration_lock")
+  check "the lock is released" '[ "$LOCKS" -eq 0 ]'
+else
+  wait $HOLDER
+fi
+
+psql "$CN" -qc "drop schema if exists ${SCHEME} cascade"
+rm -rf "${LOGS}"
+
+exit ${FAILED}
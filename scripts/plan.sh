@@ -0,0 +1,34 @@
+#!/bin/bash
+
+# Checks that list queries scan a range of the primary key instead of the whole namespace.
+# Usage: ./plan.sh [connection string] (default: local CockroachDB as in db_view.sh)
+
+CN="${1:-postgresql://root@huly.local:26257/defaultdb?sslmode=disable}"
+WS="00000000-0000-0000-0000-000000000001"
+
+R='\033[0;31m' # Color red
+G='\033[0;32m' # Color green
+N='\033[0m' # No Color
+
+if psql "$CN" -Atc "select version()" | grep -q CockroachDB; then
+  # scan spans are bounded by the prefix range
+  EXPECTED="spans: \[/'${WS}'/'Huome2'/'My%Key' - /'${WS}'/'Huome2'/'My%Kez')"
+  SETUP="SET search_path TO hulykvs"
+else
+  # tiny test tables are read sequentially otherwise
+  EXPECTED="Index Cond: .*key >= 'My%Key'.*key < 'My%Kez'"
+  SETUP="SET search_path TO hulykvs; SET enable_seqscan = off"
+fi
+
+plan() {
+  echo "-- $1"
+  local result
+  result=$(psql "$CN" -c "${SETUP}" -c "EXPLAIN $1")
+  echo "$result"
+  if echo "$result" | grep -q "$EXPECTED"; then echo -e "${G}OK: primary key range${N}"
+  else echo -e "${R}FAIL: expected ${EXPECTED}${N}"
+  fi
+}
+
+plan "select key from kvs where workspace='${WS}' and namespace='Huome2' and key >= 'My%Key' and key < 'My%Kez' order by key asc limit 101"
+plan "select key from kvs where workspace='${WS}' and namespace='Huome2' and key >= 'My%Key' and key < 'My%Kez' order by key desc limit 101"
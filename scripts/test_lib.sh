@@ -50,14 +50,45 @@ api() {
   rm -f "$tmpfile"
 }
 
-get() {
-  echo -n -e "📥 ${L}GET ${W}$1${N} > "
+get() { # If-None-Match
+  local match
+  local match_prn
+  if [ -n "$2" ]; then match=(-H "$2: $3") ; else match=() ; fi
+  if [ -n "$2" ]; then match_prn=" ${F}$2:$3${N}" ; else match_prn="" ; fi
+  echo -n -e "📥 ${L}GET ${W}$1${N}${match_prn} > "
   local tmpfile
   tmpfile=$(mktemp)
-  curl -i -s -X GET "$URL/$1" -H "Authorization: Bearer ${TOKEN}" | tr -d '\r' > "$tmpfile"
+  curl -i -s -X GET "$URL/$1" -H "Authorization: Bearer ${TOKEN}" "${match[@]}" | tr -d '\r' > "$tmpfile"
   api ${tmpfile}
 }
 
+probe() {
+  echo -n -e "📥 ${L}GET ${W}/$1${N} > "
+  local tmpfile
+  tmpfile=$(mktemp)
+  curl -i -s -X GET "${URL%/api2}/$1" | tr -d '\r' > "$tmpfile"
+  api ${tmpfile}
+}
+
+metrics() { # series name
+  echo -e "📥 ${L}GET ${W}/metrics${N} ${F}$1${N} > "
+  curl -s "${URL%/api2}/metrics" | grep "^$1" | sed "s/^/   /"
+}
+
+head_key() {
+  echo -n -e "📥 ${L}HEAD ${W}$1${N} > "
+  local tmpfile
+  tmpfile=$(mktemp)
+  curl -I -s "$URL/$1" -H "Authorization: Bearer ${TOKEN}" | tr -d '\r' > "$tmpfile"
+  local length
+  length=$(grep -i "^Content-Length:" "${tmpfile}")
+  local modified
+  modified=$(grep -i "^Last-Modified:" "${tmpfile}")
+  api ${tmpfile}
+  if [ -n "$length" ]; then echo -e "   ${GRAY}${length}${N}" ; fi
+  if [ -n "$modified" ]; then echo -e "   ${GRAY}${modified}${N}" ; fi
+}
+
 put() { # If-None-Match If-Match
   local match
   local match_prn
@@ -70,11 +101,23 @@ put() { # If-None-Match If-Match
   api ${tmpfile}
 }
 
-delete() {
-  echo -n -e "📥 ${L}DELETE ${W}$1${N} > "
+delete() { # If-Match
+  local match
+  local match_prn
+  if [ -n "$2" ]; then match=(-H "$2: $3") ; else match=() ; fi
+  if [ -n "$2" ]; then match_prn=" ${F}$2:$3${N}" ; else match_prn="" ; fi
+  echo -n -e "📥 ${L}DELETE ${W}$1${N}${match_prn} > "
   local tmpfile
   tmpfile=$(mktemp)
-  curl -i -s -X DELETE "$URL/$1" -H "Authorization: Bearer ${TOKEN}" | tr -d '\r' > "$tmpfile"
+  curl -i -s -X DELETE "$URL/$1" -H "Authorization: Bearer ${TOKEN}" "${match[@]}" | tr -d '\r' > "$tmpfile"
   api ${tmpfile}
 }
 
+
+post() { # JSON body
+  echo -n -e "📥 ${L}POST ${W}$1${N} ${GRAY}$2${N} > "
+  local tmpfile
+  tmpfile=$(mktemp)
+  curl -i -s -X POST "$URL/$1" -H "Authorization: Bearer ${TOKEN}" -H "Content-Type: application/json" -d "$2" | tr -d '\r' > "$tmpfile"
+  api ${tmpfile}
+}
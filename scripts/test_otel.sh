@@ -0,0 +1,78 @@
+#!/bin/bash
+#
+# Checks the OTLP export against a local collector (needs docker and jq).
+#
+# Start the collector, then hulykvs exporting to it, then run the checks:
+#   ./test_otel.sh collector
+#   HULY_OTEL_ENDPOINT=http://localhost:4318 cargo run --bin hulykvs
+#   ./test_otel.sh
+# and stop the collector with `./test_otel.sh stop`.
+
+source ./test_lib.sh
+
+CONTAINER="hulykvs-otel"
+OUT="/tmp/hulykvs-otel"
+IMAGE="otel/opentelemetry-collector-contrib:0.115.0"
+
+case "$1" in
+  collector)
+    mkdir -p "${OUT}" && chmod 777 "${OUT}" && rm -f "${OUT}/traces.json"
+    docker run -d --rm --name "${CONTAINER}" -p 4318:4318 \
+      -v "$(pwd)/otel/collector.yaml:/etc/otelcol-contrib/config.yaml:ro" \
+      -v "${OUT}:/out" "${IMAGE}"
+    exit $?
+    ;;
+  stop)
+    docker stop "${CONTAINER}"
+    exit $?
+    ;;
+esac
+
+TOKEN=$(./token.sh claims.json)
+KEY="otel-$(date +%s%N)"
+ZP="00000000-0000-0000-0000-000000000001/TESTS/${KEY}"
+
+put ${ZP} "traced value"
+get ${ZP}
+delete ${ZP}
+
+# attributes of the server span of $1 for the key, as a flat JSON object
+span() {
+  jq -c --arg name "$1" --arg key "${KEY}" '
+    .resourceSpans[].scopeSpans[].spans[]
+    | select(.name == $name)
+    | [.attributes[] | {key, value: (.value | to_entries[0].value)}] | from_entries
+    | select(.key == $key)' "${OUT}/traces.json" 2>/dev/null | head -1
+}
+
+# spans are exported in batches, every 5 seconds by default
+for i in $(seq 1 20); do
+  ATTRS=$(span "PUT /api2/{workspace}/{bucket}/{id}")
+  [ -n "${ATTRS}" ] && break
+  sleep 1
+done
+
+if [ -z "${ATTRS}" ]; then
+  echo -e "${R}FAIL${N} no span of the PUT request in ${OUT}/traces.json"
+  exit 1
+fi
+
+echo -e "${GRAY}${ATTRS}${N}"
+
+FAILED=0
+for expected in \
+  'workspace "00000000-0000-0000-0000-000000000001"' \
+  'namespace "TESTS"' \
+  'op "update"' \
+  'http.response.status_code "201"'; do
+  set -- ${expected}
+  actual=$(echo "${ATTRS}" | jq -c --arg field "$1" '.[$field] | tostring')
+  if [ "${actual}" == "$2" ]; then
+    echo -e "${G}OK${N} $1 = ${actual}"
+  else
+    echo -e "${R}FAIL${N} $1 = ${actual}, expected $2"
+    FAILED=1
+  fi
+done
+
+exit ${FAILED}
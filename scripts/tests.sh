@@ -6,20 +6,125 @@ source ./test_lib.sh
 TOKEN=$(./token.sh claims.json)
 ZP="00000000-0000-0000-0000-000000000001/TESTS/AnyKey"
 
+echo "================> PROBES (Expected OK)"
+    probe live
+    probe ready
+
 echo "================> LIST"
     put "00000000-0000-0000-0000-000000000001/Huome2/MyKey1" "value1"
     put "00000000-0000-0000-0000-000000000001/Huome2/MyKey2" "value2"
     get "00000000-0000-0000-0000-000000000001/Huome2"
+    echo "-- Expected OK: MyKey1 and next_cursor"
+     get "00000000-0000-0000-0000-000000000001/Huome2?limit=1"
+    echo "-- Expected OK: MyKey2, no next_cursor"
+     get "00000000-0000-0000-0000-000000000001/Huome2?limit=1&cursor=4d794b657931"
+    echo "-- Expected OK: MyKey2 and next_cursor"
+     get "00000000-0000-0000-0000-000000000001/Huome2?limit=1&order=desc"
+    echo "-- Expected Error: 400 Bad Request (cursor and start_after)"
+     get "00000000-0000-0000-0000-000000000001/Huome2?cursor=4d794b657931&start_after=MyKey1"
     delete "00000000-0000-0000-0000-000000000001/Huome2/MyKey1"
     delete "00000000-0000-0000-0000-000000000001/Huome2/MyKey2"
 
+echo "================> LIST literal prefix"
+    put "00000000-0000-0000-0000-000000000001/Huome3/a%25b" "percent"
+    put "00000000-0000-0000-0000-000000000001/Huome3/a_b" "underscore"
+    put "00000000-0000-0000-0000-000000000001/Huome3/axb" "x"
+    put "00000000-0000-0000-0000-000000000001/Huome3/%D1%8F%F0%9F%94%91" "unicode"
+    echo "-- Expected OK: a%b only"
+     get "00000000-0000-0000-0000-000000000001/Huome3?prefix=a%25"
+    echo "-- Expected OK: a_b only"
+     get "00000000-0000-0000-0000-000000000001/Huome3?prefix=a_"
+    echo "-- Expected OK: a%b, a_b, axb"
+     get "00000000-0000-0000-0000-000000000001/Huome3?prefix=a"
+    echo "-- Expected OK: я🔑 only"
+     get "00000000-0000-0000-0000-000000000001/Huome3?prefix=%D1%8F"
+    echo "-- Expected OK: no keys"
+     get "00000000-0000-0000-0000-000000000001/Huome3?prefix=%25"
+    delete "00000000-0000-0000-0000-000000000001/Huome3/a%25b"
+    delete "00000000-0000-0000-0000-000000000001/Huome3/a_b"
+    delete "00000000-0000-0000-0000-000000000001/Huome3/axb"
+    delete "00000000-0000-0000-0000-000000000001/Huome3/%D1%8F%F0%9F%94%91"
+
+echo "================> LIST values and metadata"
+    put "00000000-0000-0000-0000-000000000001/Huome4/MyKey1" "value1"
+    put "00000000-0000-0000-0000-000000000001/Huome4/MyKey2" "value2"
+    echo "-- Expected OK: items with etag, size, created and updated"
+     get "00000000-0000-0000-0000-000000000001/Huome4?metadata=true"
+    echo "-- Expected OK: items with value1 and value2 as utf8"
+     get "00000000-0000-0000-0000-000000000001/Huome4?values=raw"
+    echo "-- Expected OK: items with dmFsdWUx and dmFsdWUy as base64, ETags"
+     get "00000000-0000-0000-0000-000000000001/Huome4?values=base64&metadata=true"
+    echo "-- Expected OK: NDJSON, MyKey1 with value1, then next_cursor"
+     get "00000000-0000-0000-0000-000000000001/Huome4?values=raw&limit=1" "Accept" "application/x-ndjson"
+    echo "-- Expected Error: 400 Bad Request (unknown values)"
+     get "00000000-0000-0000-0000-000000000001/Huome4?values=hex"
+    delete "00000000-0000-0000-0000-000000000001/Huome4/MyKey1"
+    delete "00000000-0000-0000-0000-000000000001/Huome4/MyKey2"
+
+echo "================> LIST delimiter"
+    put "00000000-0000-0000-0000-000000000001/Huome5/a%2F1" "a1"
+    put "00000000-0000-0000-0000-000000000001/Huome5/a%2Fb%2F1" "ab1"
+    put "00000000-0000-0000-0000-000000000001/Huome5/b%2F1" "b1"
+    put "00000000-0000-0000-0000-000000000001/Huome5/c" "c"
+    echo "-- Expected OK: keys [c], common_prefixes [a/, b/]"
+     get "00000000-0000-0000-0000-000000000001/Huome5?delimiter=/"
+    echo "-- Expected OK: common_prefixes [a/] and next_cursor"
+     get "00000000-0000-0000-0000-000000000001/Huome5?delimiter=/&limit=1"
+    echo "-- Expected OK: common_prefixes [b/], keys [c]"
+     get "00000000-0000-0000-0000-000000000001/Huome5?delimiter=/&cursor=612f"
+    echo "-- Expected OK: keys [a/1], common_prefixes [a/b/]"
+     get "00000000-0000-0000-0000-000000000001/Huome5?delimiter=/&prefix=a/"
+    echo "-- Expected OK: NDJSON, c, b/, a/"
+     get "00000000-0000-0000-0000-000000000001/Huome5?delimiter=/&order=desc" "Accept" "application/x-ndjson"
+    delete "00000000-0000-0000-0000-000000000001/Huome5/a%2F1"
+    delete "00000000-0000-0000-0000-000000000001/Huome5/a%2Fb%2F1"
+    delete "00000000-0000-0000-0000-000000000001/Huome5/b%2F1"
+    delete "00000000-0000-0000-0000-000000000001/Huome5/c"
+
+echo "================> BATCH GET"
+    put "00000000-0000-0000-0000-000000000001/Huome6/MyKey1" "value1"
+    put "00000000-0000-0000-0000-000000000001/Huome6/MyKey2" "value2"
+    echo "-- Expected OK: MyKey1 and MyKey2 with values and ETags, MyKey3 missing"
+     post "00000000-0000-0000-0000-000000000001/Huome6:batchGet" '{"keys": ["MyKey2", "MyKey3", "MyKey1"]}'
+    echo "-- Expected OK: MyKey1 with base64 value"
+     post "00000000-0000-0000-0000-000000000001/Huome6:batchGet" '{"keys": ["MyKey1"], "values": "base64"}'
+    echo "-- Expected OK: MyKey1 with ETag, no value"
+     post "00000000-0000-0000-0000-000000000001/Huome6:batchGet" '{"keys": ["MyKey1"], "values": "none"}'
+    echo "-- Expected Error: 400 Bad Request (no keys)"
+     post "00000000-0000-0000-0000-000000000001/Huome6:batchGet" '{}'
+    delete "00000000-0000-0000-0000-000000000001/Huome6/MyKey1"
+    delete "00000000-0000-0000-0000-000000000001/Huome6/MyKey2"
+
+echo "================> TRANSACTION"
+    delete "00000000-0000-0000-0000-000000000001/Huome6/MyKey1"
+    delete "00000000-0000-0000-0000-000000000001/Huome7/MyKey2"
+    echo "-- Expected OK: MyKey1 and MyKey2 created in two namespaces"
+     post "00000000-0000-0000-0000-000000000001:transaction" '{"operations": [
+        {"op": "put", "namespace": "Huome6", "key": "MyKey1", "value": "value1", "if_none_match": "*"},
+        {"op": "put", "namespace": "Huome7", "key": "MyKey2", "value": "dmFsdWUy", "encoding": "base64"}]}'
+    echo "-- Expected Error: 412 Precondition Failed (operation 1), MyKey1 stays value1"
+     post "00000000-0000-0000-0000-000000000001:transaction" '{"operations": [
+        {"op": "put", "namespace": "Huome6", "key": "MyKey1", "value": "value3"},
+        {"op": "check", "namespace": "Huome7", "key": "MyKey2", "if_match": "\"552e21cd4cd9918678e3c1a0df491bc3\""}]}'
+    get "00000000-0000-0000-0000-000000000001/Huome6/MyKey1"
+    echo "-- Expected OK: MyKey1 deleted, MyKey2 checked"
+     post "00000000-0000-0000-0000-000000000001:transaction" '{"operations": [
+        {"op": "delete", "namespace": "Huome6", "key": "MyKey1", "if_match": "\"9946687e5fa0dab5993ededddb398d2e\""},
+        {"op": "check", "namespace": "Huome7", "key": "MyKey2", "if_match": "*"}]}'
+    echo "-- Expected Error: 400 Bad Request (value with delete)"
+     post "00000000-0000-0000-0000-000000000001:transaction" '{"operations": [
+        {"op": "delete", "namespace": "Huome7", "key": "MyKey2", "value": "x"}]}'
+    delete "00000000-0000-0000-0000-000000000001/Huome7/MyKey2"
+
 echo "================> WRONG UUID"
     get "WrongUUID/TESTS/AnyKey"
 
 echo "================> INSERT If-None-Match"
 
-    echo "-- Expected Error: 400 Bad Request (If-None-Match may be only *)"
-     put ${ZP} "enother text" "If-None-Match" "552e21cd4cd9918678e3c1a0df491bc3"
+    echo "-- Expected Error: 400 Bad Request (malformed ETag)"
+     put ${ZP} "enother text" "If-None-Match" '"552e21cd4cd9918678e3c1a0df491bc3'
+    echo "-- Expected Error: 400 Bad Request (* in a list)"
+     put ${ZP} "enother text" "If-Match" '*, "552e21cd4cd9918678e3c1a0df491bc3"'
 
     delete ${ZP}
 
@@ -54,10 +159,84 @@ echo "================> UPDATE PUT If-Match"
     echo "-- Expected Error: 412 Precondition Failed (any hash not found)"
      put ${ZP} "enother version2" "If-Match" "*"
 
+echo "================> PUT ETag lists"
+    put ${ZP} "some text"
+
+    echo "-- Expected OK: 204 No Content (one of the quoted hashes)"
+     put ${ZP} "some text" "If-Match" '"65587139deb71a30e40bd82679f0990f", "552e21cd4cd9918678e3c1a0df491bc3"'
+    echo "-- Expected Error: 412 Precondition Failed (weak ETag never matches If-Match)"
+     put ${ZP} "some text" "If-Match" 'W/"552e21cd4cd9918678e3c1a0df491bc3"'
+    echo "-- Expected Error: 412 Precondition Failed (If-None-Match hash is current)"
+     put ${ZP} "enother text" "If-None-Match" 'W/"552e21cd4cd9918678e3c1a0df491bc3"'
+    echo "-- Expected OK: 204 No Content (If-None-Match hash is stale)"
+     put ${ZP} "enother text" "If-None-Match" '"65587139deb71a30e40bd82679f0990f"'
+    echo "-- Expected Error: 412 Precondition Failed (a comma within a quoted tag does not split it)"
+     put ${ZP} "some text" "If-Match" '"552e21cd4cd9918678e3c1a0df491bc3,x"'
+    echo "-- Expected OK: 204 No Content (quoted tag with a comma, then the current hash)"
+     put ${ZP} "some text" "If-Match" '"a,b", "c038beebd34c1c23ecee39b9a893fe62"'
+    echo "-- Expected Error: 400 Bad Request (unterminated quote)"
+     put ${ZP} "some text" "If-Match" '"a,b'
+
+    delete ${ZP}
+
 echo "================> UPSERT (Expected OK)"
     put ${ZP} "my value"
     get ${ZP}
     put ${ZP} "my new value"
     get ${ZP}
 
+echo "================> GET If-None-Match"
+    echo "-- Expected OK: 304 Not Modified (same hash)"
+     get ${ZP} "If-None-Match" '"65587139deb71a30e40bd82679f0990f"'
+    echo "-- Expected OK: 304 Not Modified (one of the hashes)"
+     get ${ZP} "If-None-Match" '"552e21cd4cd9918678e3c1a0df491bc3", W/"65587139deb71a30e40bd82679f0990f"'
+    echo "-- Expected OK: 304 Not Modified (any value)"
+     get ${ZP} "If-None-Match" "*"
+    echo "-- Expected OK: 200 OK (hash changed)"
+     get ${ZP} "If-None-Match" '"552e21cd4cd9918678e3c1a0df491bc3"'
+    echo "-- Expected Error: 400 Bad Request (malformed ETag)"
+     get ${ZP} "If-None-Match" '"552e21cd'
+
+echo "================> DELETE If-Match"
+    echo "-- Expected Error: 412 Precondition Failed (wrong hash)"
+     delete ${ZP} "If-Match" "552e21cd4cd9918678e3c1a0df491bc3"
+    echo "-- Expected Error: 412 Precondition Failed (weak ETag)"
+     delete ${ZP} "If-Match" 'W/"65587139deb71a30e40bd82679f0990f"'
+    echo "-- Expected OK: 204 No Content (right hash)"
+     delete ${ZP} "If-Match" '"65587139deb71a30e40bd82679f0990f"'
+    echo "-- Expected Error: 412 Precondition Failed (any hash not found)"
+     delete ${ZP} "If-Match" "*"
+    put ${ZP} "my new value"
+
+echo "================> HEAD"
+    echo "-- Expected OK: 200 with ETag, Content-Length: 12 and Last-Modified"
+     head_key ${ZP}
+    delete ${ZP}
+    echo "-- Expected Error: 404 Not Found"
+     head_key ${ZP}
+
+echo "================> ACCESS CONTROL (kvs claim: rl:public*,rwl:TESTS)"
+    TOKEN=$(./token.sh claims_kvs.json)
+    delete "00000000-0000-0000-0000-000000000001/TESTS/Granted"
+    echo "-- Expected OK: 204 No Content, then 200 (rwl on TESTS)"
+     put "00000000-0000-0000-0000-000000000001/TESTS/Granted" "granted"
+     get "00000000-0000-0000-0000-000000000001/TESTS/Granted"
+    echo "-- Expected OK: 404 Not Found (read on public1)"
+     get "00000000-0000-0000-0000-000000000001/public1/Granted"
+    echo "-- Expected Error: 403 No write access to namespace public1: not granted by token"
+     put "00000000-0000-0000-0000-000000000001/public1/Denied" "denied"
+    echo "-- Expected Error: 403 No read access to namespace private: not granted by token"
+     get "00000000-0000-0000-0000-000000000001/private/Denied"
+    echo "-- Expected Error: 403 No list access to namespace private: not granted by token"
+     get "00000000-0000-0000-0000-000000000001/private"
+     delete "00000000-0000-0000-0000-000000000001/TESTS/Granted"
+    TOKEN=$(./token.sh claims.json)
+
+echo "================> METRICS"
+    echo "-- Expected: 412 responses of the tests above"
+     metrics hulykvs_precondition_failed_total
+     metrics hulykvs_pool_
+    echo "-- Expected: 0 bytes sent for HEAD requests"
+     metrics 'hulykvs_http_response_bytes_total{method="HEAD"'
+
 exit